@@ -0,0 +1,392 @@
+mod mints;
+mod rpc;
+
+use clap::{crate_description, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use nft_staking_sdk::{instruction, pda, state, CountBonus, FundMode};
+use rpc::{Result, RpcClient};
+use serde_json::json;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+use std::path::Path;
+use std::str::FromStr;
+
+const DEFAULT_URL: &str = "http://localhost:8899";
+const DEFAULT_CHUNK_SIZE: &str = "20";
+
+struct Context {
+    rpc: RpcClient,
+    payer: Keypair,
+    dry_run: bool,
+}
+
+impl Context {
+    // sign with the payer, simulate in dry run mode or send otherwise
+    fn process(&self, instructions: &[Instruction]) -> Result<()> {
+        if self.dry_run {
+            return self.simulate(instructions);
+        }
+        let transaction = self.transaction(instructions)?;
+        let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+        println!("Signature: {}", signature);
+        Ok(())
+    }
+
+    // simulate and print the program logs
+    fn simulate(&self, instructions: &[Instruction]) -> Result<()> {
+        let simulation = self.rpc.simulate_transaction(&self.transaction(instructions)?)?;
+        for log in &simulation.logs {
+            println!("  {}", log);
+        }
+        match simulation.err {
+            Some(err) => Err(format!("simulation failed: {}", err).into()),
+            None => {
+                println!("Simulation succeeded");
+                Ok(())
+            }
+        }
+    }
+
+    fn transaction(&self, instructions: &[Instruction]) -> Result<Transaction> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        Ok(Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &[&self.payer],
+            blockhash,
+        ))
+    }
+
+    fn pool(&self, pool_account: &Pubkey) -> Result<nft_staking::Pool> {
+        let data = self.rpc.get_account_data(pool_account)?.ok_or("pool account not found")?;
+        Ok(state::pool(&data)?)
+    }
+}
+
+fn pubkey_arg(matches: &ArgMatches, name: &str) -> Result<Pubkey> {
+    let value = matches.value_of(name).ok_or_else(|| format!("missing --{}", name))?;
+    Pubkey::from_str(value).map_err(|err| format!("invalid --{} {}: {}", name, value, err).into())
+}
+
+fn number_arg<T: FromStr>(matches: &ArgMatches, name: &str) -> Result<T> {
+    let value = matches.value_of(name).ok_or_else(|| format!("missing --{}", name))?;
+    value.parse().map_err(|_| format!("invalid --{} {}", name, value).into())
+}
+
+fn pool_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("pool").long("pool").takes_value(true).required(true).help("Pool account address")
+}
+
+// same uuid scheme as the js client, first 6 characters of a random pubkey
+fn generate_uuid() -> String {
+    Keypair::new().pubkey().to_string()[..6].to_string()
+}
+
+fn init_pool(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let reward_mint = pubkey_arg(matches, "reward_mint")?;
+    let reward_duration: u64 = number_arg(matches, "reward_duration")?;
+    let num_mint: u32 = match matches.value_of("mints") {
+        Some(path) => mints::read_mint_addresses(Path::new(path))?.len() as u32,
+        None => number_arg(matches, "num_mint")?,
+    };
+
+    let authority = ctx.payer.pubkey();
+    let uuid = generate_uuid();
+    let config = pda::config_address(&authority, &uuid)?;
+    let (pool_account, _) = pda::pool_address(&authority, &config);
+    let mint_group_space: usize = number_arg(matches, "mint_group_space")?;
+    let lamports = ctx.rpc.get_minimum_balance_for_rent_exemption(state::config_space(num_mint) + mint_group_space)?;
+
+    println!("Config: {}", config);
+    println!("Pool: {}", pool_account);
+    ctx.process(&[
+        instruction::create_config_account_with_mint_groups(&authority, &uuid, lamports, num_mint, mint_group_space),
+        instruction::initialize_pool(&authority, &config, &reward_mint, &uuid, num_mint, reward_duration),
+    ])
+}
+
+fn add_mints(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let pool = ctx.pool(&pool_account)?;
+    let mint_addresses = mints::read_mint_addresses(Path::new(matches.value_of("file").unwrap()))?;
+    let chunk_size: usize = number_arg(matches, "chunk_size")?;
+    let start_index: usize = number_arg(matches, "start_index")?;
+    if chunk_size == 0 {
+        return Err("--chunk-size must be greater than zero".into());
+    }
+
+    for (chunk_index, chunk) in mint_addresses[start_index..].chunks(chunk_size).enumerate() {
+        let index = start_index + chunk_index * chunk_size;
+        println!("Adding mint addresses {}..{}", index, index + chunk.len());
+        ctx.process(&[instruction::add_mint_addresses(
+            &ctx.payer.pubkey(),
+            &pool_account,
+            &pool.config,
+            chunk.to_vec(),
+            index as u32,
+        )])?;
+    }
+    Ok(())
+}
+
+fn fund(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let pool = ctx.pool(&pool_account)?;
+    let funder_vault = pubkey_arg(matches, "funder_vault")?;
+    let amount: u64 = number_arg(matches, "amount")?;
+    let mode = if matches.is_present("top_up") { FundMode::TopUp } else { FundMode::Reset };
+    // the preview logs the resulting rate and end without transferring
+    if matches.is_present("preview") {
+        return ctx.simulate(&[instruction::preview_fund(&pool_account, &pool, amount, mode)]);
+    }
+    ctx.process(&[instruction::fund(&ctx.payer.pubkey(), &pool_account, &pool, &funder_vault, amount, mode)])
+}
+
+fn donate(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let pool = ctx.pool(&pool_account)?;
+    let donor_vault = pubkey_arg(matches, "donor_vault")?;
+    let amount: u64 = number_arg(matches, "amount")?;
+    ctx.process(&[instruction::donate(&ctx.payer.pubkey(), &pool_account, &pool, &donor_vault, amount)])
+}
+
+fn set_loyalty_ramp(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let step: u64 = number_arg(matches, "step")?;
+    let step_bps: u32 = number_arg(matches, "step_bps")?;
+    let max_bps: u32 = number_arg(matches, "max_bps")?;
+    ctx.process(&[instruction::set_loyalty_ramp(&ctx.payer.pubkey(), &pool_account, step, step_bps, max_bps)])
+}
+
+// rules given as MIN_COUNT:BONUS_BPS, no rule clears them
+fn set_count_bonus(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let mut rules = vec![];
+    for rule in matches.values_of("rule").into_iter().flatten() {
+        let (min_count, bonus_bps) = rule.split_once(':').ok_or_else(|| format!("invalid --rule {}, expected MIN_COUNT:BONUS_BPS", rule))?;
+        rules.push(CountBonus {
+            min_count: min_count.parse().map_err(|_| format!("invalid --rule {}", rule))?,
+            bonus_bps: bonus_bps.parse().map_err(|_| format!("invalid --rule {}", rule))?,
+        });
+    }
+    ctx.process(&[instruction::set_count_bonus(&ctx.payer.pubkey(), &pool_account, rules)])
+}
+
+fn add_mint_group(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let pool = ctx.pool(&pool_account)?;
+    let bonus_bps: u32 = number_arg(matches, "bonus_bps")?;
+    let mints = mints::read_mint_addresses(Path::new(matches.value_of("file").unwrap()))?;
+    ctx.process(&[instruction::add_mint_group(&ctx.payer.pubkey(), &pool_account, &pool.config, bonus_bps, mints)])
+}
+
+fn clear_mint_groups(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let pool = ctx.pool(&pool_account)?;
+    ctx.process(&[instruction::clear_mint_groups(&ctx.payer.pubkey(), &pool_account, &pool.config)])
+}
+
+fn inspect_pool(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let pool = ctx.pool(&pool_account)?;
+    println!("Pool: {}", pool_account);
+    println!("  initialized: {}", pool.is_initialized);
+    println!("  authority: {}", pool.authority);
+    println!("  paused: {}", pool.paused);
+    println!("  config: {}", pool.config);
+    println!("  reward mint: {}", pool.reward_mint);
+    println!("  reward vault: {}", pool.reward_vault);
+    println!("  last update time: {}", pool.last_update_time);
+    println!("  reward rate per token: {}", pool.reward_rate_per_token);
+    println!("  reward duration: {}", pool.reward_duration);
+    println!("  reward duration end: {}", pool.reward_duration_end);
+    println!("  tokens staked: {}", pool.token_stake_count);
+    println!("  users: {}", pool.user_count);
+    println!("  paused duration: {}", pool.paused_duration);
+    println!("  peak tokens staked: {}", pool.peak_staked);
+    println!("  total funded: {}", pool.total_funded);
+    println!("  total claimed: {}", pool.total_claimed);
+    println!("  total owed (estimate): {}", pool.total_owed_estimate);
+    println!("  last fund time: {}", pool.last_fund_time);
+    println!("  total donated: {}", pool.total_donated);
+    if pool.loyalty_step > 0 {
+        println!("  loyalty ramp: +{} bps per {}s, up to +{} bps", pool.loyalty_step_bps, pool.loyalty_step, pool.loyalty_max_bps);
+    }
+    for rule in pool.count_bonus.iter().filter(|x| x.min_count > 0) {
+        println!("  count bonus: +{} bps from {} staked", rule.bonus_bps, rule.min_count);
+    }
+    if pool.mint_group_bonus_bps > 0 {
+        println!("  mint group bonus: up to +{} bps", pool.mint_group_bonus_bps);
+    }
+    println!("  layout version: {}", pool.version);
+    println!("  funders: {}", pool.funder_count);
+    // funder accounts start with the pool after the discriminator
+    let filters = json!([
+        {"dataSize": nft_staking::FUNDER_SIZE},
+        {"memcmp": {"offset": 8, "bytes": pool_account.to_string()}},
+    ]);
+    for (_, data) in ctx.rpc.get_program_accounts(&nft_staking::ID, filters)? {
+        let funder = state::funder(&data)?;
+        let status = if funder.authorized { "" } else { " (deauthorized)" };
+        println!("  funder: {} funded {}/{}{}", funder.funder, funder.funded, funder.cap, status);
+    }
+    for funder in pool.legacy_funders.iter().filter(|x| **x != Pubkey::default()) {
+        println!("  legacy funder, authorize again to fund: {}", funder);
+    }
+
+    if let Some(data) = ctx.rpc.get_account_data(&pool.config)? {
+        let config = state::config(&data)?;
+        let mint_addresses = state::config_mint_addresses(&data)?;
+        println!("Config: {}", pool.config);
+        println!("  uuid: {}", config.uuid);
+        println!("  mint addresses: {}/{}", mint_addresses.len(), config.num_mint);
+        for (index, group) in state::mint_groups(&data)?.iter().enumerate() {
+            println!("  mint group {}: +{} bps for {} mints", index, group.bonus_bps, group.mints.len());
+        }
+    }
+    Ok(())
+}
+
+fn close_pool(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let pool = ctx.pool(&pool_account)?;
+    let reward_refundee = pubkey_arg(matches, "reward_refundee")?;
+    let refundee = match matches.value_of("refundee") {
+        Some(_) => pubkey_arg(matches, "refundee")?,
+        None => ctx.payer.pubkey(),
+    };
+    // funders are refunded to their associated token account
+    let funders = matches
+        .values_of("refund_funder")
+        .map(|values| values.collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|value| {
+            let funder = Pubkey::from_str(value).map_err(|err| format!("invalid --refund-funder {}: {}", value, err))?;
+            Ok((funder, pda::associated_token_address(&funder, &pool.reward_mint)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ctx.process(&[instruction::close_pool(&ctx.payer.pubkey(), &pool_account, &pool, &refundee, &reward_refundee, &funders)])
+}
+
+// recreate a pool written with an older account layout, the pool is closed between the two transactions
+fn migrate_pool(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let pool_account = pubkey_arg(matches, "pool")?;
+    let data = ctx.rpc.get_account_data(&pool_account)?.ok_or("pool account not found")?;
+    if data.len() >= nft_staking::POOL_SIZE {
+        println!("Pool {} is already on the current layout", pool_account);
+        return Ok(());
+    }
+    let pool = state::older_pool(&data)?;
+    ctx.process(&[instruction::begin_migrate_pool(&ctx.payer.pubkey(), &pool_account)])?;
+    ctx.process(&[instruction::migrate_pool(&ctx.payer.pubkey(), &pool.config)])
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let keypair_path = match matches.value_of("keypair") {
+        Some(path) => path.to_string(),
+        None => {
+            let home = std::env::var("HOME").map_err(|_| "missing --keypair and $HOME")?;
+            format!("{}/.config/solana/id.json", home)
+        }
+    };
+    let ctx = Context {
+        rpc: RpcClient::new(matches.value_of("url").unwrap()),
+        payer: read_keypair_file(&keypair_path).map_err(|err| format!("failed to read {}: {}", keypair_path, err))?,
+        dry_run: matches.is_present("dry_run"),
+    };
+    let authority = ctx.payer.pubkey();
+
+    match matches.subcommand() {
+        ("init-pool", Some(m)) => init_pool(&ctx, m),
+        ("add-mints", Some(m)) => add_mints(&ctx, m),
+        ("fund", Some(m)) => fund(&ctx, m),
+        ("donate", Some(m)) => donate(&ctx, m),
+        ("authorize-funder", Some(m)) => ctx.process(&[instruction::authorize_funder(&authority, &pubkey_arg(m, "pool")?, &pubkey_arg(m, "funder")?, number_arg(m, "cap")?)]),
+        ("set-funder-cap", Some(m)) => ctx.process(&[instruction::set_funder_cap(&authority, &pubkey_arg(m, "pool")?, &pubkey_arg(m, "funder")?, number_arg(m, "cap")?)]),
+        ("deauthorize-funder", Some(m)) => ctx.process(&[instruction::deauthorize_funder(&authority, &pubkey_arg(m, "pool")?, &pubkey_arg(m, "funder")?)]),
+        ("pause", Some(m)) => ctx.process(&[instruction::pause(&authority, &pubkey_arg(m, "pool")?)]),
+        ("resume", Some(m)) => ctx.process(&[instruction::resume(&authority, &pubkey_arg(m, "pool")?)]),
+        ("set-loyalty-ramp", Some(m)) => set_loyalty_ramp(&ctx, m),
+        ("set-count-bonus", Some(m)) => set_count_bonus(&ctx, m),
+        ("add-mint-group", Some(m)) => add_mint_group(&ctx, m),
+        ("clear-mint-groups", Some(m)) => clear_mint_groups(&ctx, m),
+        ("inspect-pool", Some(m)) => inspect_pool(&ctx, m),
+        ("close-pool", Some(m)) => close_pool(&ctx, m),
+        ("migrate-pool", Some(m)) => migrate_pool(&ctx, m),
+        _ => unreachable!(),
+    }
+}
+
+fn main() {
+    let funder_arg = Arg::with_name("funder").long("funder").takes_value(true).required(true).help("Funder address");
+    let cap_arg = Arg::with_name("cap").long("cap").takes_value(true).required(true).help("Cap of the total amount funded, in base units");
+    let matches = App::new("nft-staking-admin")
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(Arg::with_name("url").long("url").short("u").takes_value(true).default_value(DEFAULT_URL).global(true).help("JSON RPC URL of the cluster"))
+        .arg(Arg::with_name("keypair").long("keypair").short("k").takes_value(true).global(true).help("Authority/payer keypair, defaults to ~/.config/solana/id.json"))
+        .arg(Arg::with_name("dry_run").long("dry-run").global(true).help("Simulate the transactions instead of sending them"))
+        .subcommand(SubCommand::with_name("init-pool")
+            .about("Create the config account and initialize a paused pool")
+            .arg(Arg::with_name("reward_mint").long("reward-mint").takes_value(true).required(true).help("Reward token mint"))
+            .arg(Arg::with_name("reward_duration").long("reward-duration").takes_value(true).required(true).help("Reward duration in seconds"))
+            .arg(Arg::with_name("num_mint").long("num-mint").takes_value(true).required_unless("mints").help("Number of stakable mints"))
+            .arg(Arg::with_name("mints").long("mints").takes_value(true).help("JSON/CSV file of stakable mints, used to size the config"))
+            .arg(Arg::with_name("mint_group_space").long("mint-group-space").takes_value(true).default_value("0").help("Config bytes reserved for mint groups, 4 plus 8 + 32 per mint for each group")))
+        .subcommand(SubCommand::with_name("add-mints")
+            .about("Upload stakable mint addresses into the config in chunks")
+            .arg(pool_arg())
+            .arg(Arg::with_name("file").long("file").takes_value(true).required(true).help("JSON array or CSV file of mint addresses"))
+            .arg(Arg::with_name("chunk_size").long("chunk-size").takes_value(true).default_value(DEFAULT_CHUNK_SIZE).help("Mint addresses per transaction"))
+            .arg(Arg::with_name("start_index").long("start-index").takes_value(true).default_value("0").help("Resume the upload from this index")))
+        .subcommand(SubCommand::with_name("fund")
+            .about("Fund the pool reward vault")
+            .arg(pool_arg())
+            .arg(Arg::with_name("funder_vault").long("funder-vault").takes_value(true).required(true).help("Funder reward token account"))
+            .arg(Arg::with_name("amount").long("amount").takes_value(true).required(true).help("Amount in base units"))
+            .arg(Arg::with_name("top_up").long("top-up").help("Keep the reward rate and extend the reward period instead of restarting it"))
+            .arg(Arg::with_name("preview").long("preview").help("Print the resulting reward rate and period end without funding")))
+        .subcommand(SubCommand::with_name("donate")
+            .about("Add rewards to the pool at the current rate, no authorization needed")
+            .arg(pool_arg())
+            .arg(Arg::with_name("donor_vault").long("donor-vault").takes_value(true).required(true).help("Donor reward token account"))
+            .arg(Arg::with_name("amount").long("amount").takes_value(true).required(true).help("Amount in base units")))
+        .subcommand(SubCommand::with_name("authorize-funder").about("Authorize a funder").arg(pool_arg()).arg(funder_arg.clone()).arg(cap_arg.clone()))
+        .subcommand(SubCommand::with_name("set-funder-cap").about("Change the cap of a funder").arg(pool_arg()).arg(funder_arg.clone()).arg(cap_arg))
+        .subcommand(SubCommand::with_name("deauthorize-funder").about("Deauthorize a funder").arg(pool_arg()).arg(funder_arg))
+        .subcommand(SubCommand::with_name("pause").about("Pause the pool").arg(pool_arg()))
+        .subcommand(SubCommand::with_name("resume").about("Resume the pool").arg(pool_arg()))
+        .subcommand(SubCommand::with_name("set-loyalty-ramp")
+            .about("Set the earning weight added by continuous staking time")
+            .arg(pool_arg())
+            .arg(Arg::with_name("step").long("step").takes_value(true).required(true).help("Seconds per loyalty step, 0 disables the bonus"))
+            .arg(Arg::with_name("step_bps").long("step-bps").takes_value(true).required(true).help("Weight added per step in basis points"))
+            .arg(Arg::with_name("max_bps").long("max-bps").takes_value(true).required(true).help("Cap of the added weight in basis points")))
+        .subcommand(SubCommand::with_name("set-count-bonus")
+            .about("Replace the bonus rules for staking several nfts")
+            .arg(pool_arg())
+            .arg(Arg::with_name("rule").long("rule").takes_value(true).multiple(true).number_of_values(1).help("MIN_COUNT:BONUS_BPS, repeat for each rule, none clears the rules")))
+        .subcommand(SubCommand::with_name("add-mint-group")
+            .about("Add a group of mints that earns a bonus when all are staked by one user")
+            .arg(pool_arg())
+            .arg(Arg::with_name("bonus_bps").long("bonus-bps").takes_value(true).required(true).help("Bonus on the base reward in basis points"))
+            .arg(Arg::with_name("file").long("file").takes_value(true).required(true).help("JSON array or CSV file of the group mint addresses")))
+        .subcommand(SubCommand::with_name("clear-mint-groups").about("Remove every mint group").arg(pool_arg()))
+        .subcommand(SubCommand::with_name("inspect-pool").about("Print the pool and config state").arg(pool_arg()))
+        .subcommand(SubCommand::with_name("close-pool")
+            .about("Refund the reward vault and close the pool")
+            .arg(pool_arg())
+            .arg(Arg::with_name("reward_refundee").long("reward-refundee").takes_value(true).required(true).help("Token account receiving the remaining rewards"))
+            .arg(Arg::with_name("refundee").long("refundee").takes_value(true).help("Account receiving the rent, defaults to the authority"))
+            .arg(Arg::with_name("refund_funder").long("refund-funder").takes_value(true).multiple(true).number_of_values(1).help("Funder refunded its share of the vault to its associated token account, repeat for each funder")))
+        .subcommand(SubCommand::with_name("migrate-pool").about("Move a pool written with an older account layout to the current one").arg(pool_arg()))
+        .get_matches();
+
+    if let Err(err) = run(&matches) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,31 @@
+use crate::rpc::Result;
+use solana_sdk::pubkey::Pubkey;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// read mint addresses from a JSON array (like client/token_mints.json) or a CSV file (address in the first column)
+pub fn read_mint_addresses(path: &Path) -> Result<Vec<Pubkey>> {
+    let content = fs::read_to_string(path)?;
+    let addresses: Vec<String> = if path.extension().is_some_and(|x| x == "json") {
+        serde_json::from_str(&content)?
+    } else {
+        content
+            .lines()
+            .filter_map(|line| line.split(',').next())
+            .map(|x| x.trim().trim_matches('"').to_string())
+            .filter(|x| !x.is_empty())
+            .collect()
+    };
+
+    let mut mints = vec![];
+    for (line, address) in addresses.iter().enumerate() {
+        match Pubkey::from_str(address) {
+            Ok(mint) => mints.push(mint),
+            // allow a header row in CSV files
+            Err(_) if line == 0 => continue,
+            Err(err) => return Err(format!("invalid mint address {:?}: {}", address, err).into()),
+        }
+    }
+    Ok(mints)
+}
@@ -0,0 +1,116 @@
+// minimal JSON-RPC client, solana-client pulls hidapi/libudev which the admin tool doesn't need
+use serde_json::{json, Value};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use std::error::Error;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::Duration;
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const CONFIRM_RETRIES: usize = 60;
+
+pub struct RpcClient {
+    url: String,
+}
+
+pub struct Simulation {
+    pub err: Option<Value>,
+    pub logs: Vec<String>,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Self {
+        RpcClient { url: url.to_string() }
+    }
+
+    fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let response: Value = ureq::post(&self.url)
+            .send_json(json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}))?
+            .into_json()?;
+        if let Some(err) = response.get("error") {
+            return Err(format!("{} failed: {}", method, err).into());
+        }
+        Ok(response["result"].clone())
+    }
+
+    pub fn get_account_data(&self, pubkey: &Pubkey) -> Result<Option<Vec<u8>>> {
+        let result = self.request(
+            "getAccountInfo",
+            json!([pubkey.to_string(), {"encoding": "base64", "commitment": "confirmed"}]),
+        )?;
+        match result["value"]["data"][0].as_str() {
+            Some(data) => Ok(Some(base64::decode(data)?)),
+            None => Ok(None),
+        }
+    }
+
+    // accounts of the program matching the getProgramAccounts filters
+    pub fn get_program_accounts(&self, program_id: &Pubkey, filters: Value) -> Result<Vec<(Pubkey, Vec<u8>)>> {
+        let result = self.request(
+            "getProgramAccounts",
+            json!([program_id.to_string(), {"encoding": "base64", "commitment": "confirmed", "filters": filters}]),
+        )?;
+        let mut accounts = vec![];
+        for account in result.as_array().ok_or("invalid program accounts")? {
+            let pubkey = Pubkey::from_str(account["pubkey"].as_str().ok_or("missing pubkey")?)?;
+            let data = account["account"]["data"][0].as_str().ok_or("missing account data")?;
+            accounts.push((pubkey, base64::decode(data)?));
+        }
+        Ok(accounts)
+    }
+
+    pub fn get_latest_blockhash(&self) -> Result<Hash> {
+        let result = self.request("getLatestBlockhash", json!([{"commitment": "confirmed"}]))?;
+        let blockhash = result["value"]["blockhash"].as_str().ok_or("missing blockhash")?;
+        Ok(Hash::from_str(blockhash)?)
+    }
+
+    pub fn get_minimum_balance_for_rent_exemption(&self, space: usize) -> Result<u64> {
+        let result = self.request("getMinimumBalanceForRentExemption", json!([space]))?;
+        result.as_u64().ok_or_else(|| "invalid rent exemption balance".into())
+    }
+
+    pub fn simulate_transaction(&self, transaction: &Transaction) -> Result<Simulation> {
+        let result = self.request(
+            "simulateTransaction",
+            json!([base64::encode(bincode::serialize(transaction)?), {"encoding": "base64", "sigVerify": true}]),
+        )?;
+        let err = match &result["value"]["err"] {
+            Value::Null => None,
+            err => Some(err.clone()),
+        };
+        let logs = result["value"]["logs"]
+            .as_array()
+            .map(|logs| logs.iter().filter_map(|x| x.as_str().map(String::from)).collect())
+            .unwrap_or_default();
+        Ok(Simulation { err, logs })
+    }
+
+    // send and wait until the transaction is confirmed
+    pub fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        let result = self.request(
+            "sendTransaction",
+            json!([base64::encode(bincode::serialize(transaction)?), {"encoding": "base64"}]),
+        )?;
+        let signature = Signature::from_str(result.as_str().ok_or("missing signature")?)?;
+        for _ in 0..CONFIRM_RETRIES {
+            let statuses = self.request("getSignatureStatuses", json!([[signature.to_string()]]))?;
+            let status = &statuses["value"][0];
+            if !status.is_null() {
+                if !status["err"].is_null() {
+                    return Err(format!("transaction {} failed: {}", signature, status["err"]).into());
+                }
+                let confirmation = status["confirmationStatus"].as_str().unwrap_or_default();
+                if confirmation == "confirmed" || confirmation == "finalized" {
+                    return Ok(signature);
+                }
+            }
+            sleep(Duration::from_millis(500));
+        }
+        Err(format!("transaction {} was not confirmed", signature).into())
+    }
+}
@@ -45,6 +45,7 @@ const PREFIX = "nft_staking";
 const PREFIX_CONFIG = "nft_staking_config";
 const PREFIX_USER = "nft_staking_user"
 const PREFIX_MINT = "nft_staking_mint"
+const PREFIX_MIGRATION = "nft_staking_migration"
 
 export interface Pool {
     id: anchor.web3.PublicKey,
@@ -88,6 +89,13 @@ interface UserState {
 interface MintStakedState {
     userAccount: anchor.web3.PublicKey; // user account address
     mintAccounts: anchor.web3.PublicKey[];
+    stakeHistory: StakeHistoryState[];
+}
+
+interface StakeHistoryState {
+    mint: anchor.web3.PublicKey;
+    stakedAt: number; // 0 while unstaked
+    stakedSeconds: number; // seconds staked before the current stake
 }
 
 export interface TokenInfo {
@@ -178,9 +186,15 @@ export const getUserState = async (
 
     let mintStakedState: MintStakedState = {
         userAccount: userAccount,
-        mintAccounts: []
+        mintAccounts: [],
+        stakeHistory: []
     }
     mintAccounts.forEach((e) => mintStakedState.mintAccounts.push(e));
+    mintStakedRes.stakeHistory.forEach((e) => mintStakedState.stakeHistory.push({
+        mint: e.mint,
+        stakedAt: e.stakedAt.toNumber(),
+        stakedSeconds: e.stakedSeconds.toNumber(),
+    }));
 
     return {
         user,
@@ -229,6 +243,25 @@ async function getOrCreateAssociatedTokenAccountInfo(
 }
 
 
+// optional remaining accounts of unstake and close_user, pending rewards are claimed in the same instruction
+const rewardRemainingAccounts = (
+    rewardVault?: anchor.web3.PublicKey,
+    rewardToAccount?: anchor.web3.PublicKey,
+    withTokenProgram: boolean = false,
+): Array<anchor.web3.AccountMeta> => {
+    if (rewardVault == null || rewardToAccount == null) {
+        return []
+    }
+    let accounts = [
+        {pubkey: rewardVault, isWritable: true, isSigner: false},
+        {pubkey: rewardToAccount, isWritable: true, isSigner: false},
+    ]
+    if (withTokenProgram) {
+        accounts.push({pubkey: TOKEN_PROGRAM_ID, isWritable: false, isSigner: false})
+    }
+    return accounts
+}
+
 const generateUuid = (): string => {
     return anchor.web3.Keypair.generate()
         .publicKey.toBase58()
@@ -315,6 +348,19 @@ const getMintStakedAccount = async (
     );
 };
 
+// migration account holding the old data of a pool or user account during its migration
+const getMigrationAccount = async (
+    account: anchor.web3.PublicKey,
+): Promise<[anchor.web3.PublicKey, number]> => {
+    return (
+        await anchor.web3.PublicKey.findProgramAddress(
+            [Buffer.from(anchor.utils.bytes.utf8.encode(PREFIX_MIGRATION)),
+                account.toBuffer(),],
+            STAKE_PROGRAM
+        )
+    );
+};
+
 const getTokensByUser = async (
     connection: anchor.web3.Connection,
     user: anchor.web3.PublicKey,
@@ -377,7 +423,10 @@ export const closeUser = async (
     program: anchor.Program,
     userWallet: anchor.web3.Keypair,
     poolAccount: anchor.web3.PublicKey,
-    userAccount: anchor.web3.PublicKey
+    userAccount: anchor.web3.PublicKey,
+    mintStaked: anchor.web3.PublicKey,
+    rewardVault?: anchor.web3.PublicKey,  // pass with rewardToAccount to claim the pending rewards
+    rewardToAccount?: anchor.web3.PublicKey,
 ): Promise<any> => {
     return await program.rpc.closeUser(
         {
@@ -385,6 +434,28 @@ export const closeUser = async (
                 user: userWallet.publicKey,
                 poolAccount: poolAccount,
                 userAccount: userAccount,
+                mintStaked: mintStaked,
+            },
+            remainingAccounts: rewardRemainingAccounts(rewardVault, rewardToAccount, true),
+            signers: [userWallet],
+        });
+}
+
+// reclaim the rent of a mint staked account the user account no longer references
+export const closeMintStaked = async (
+    program: anchor.Program,
+    userWallet: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,
+    userAccount: anchor.web3.PublicKey,
+    mintStaked: anchor.web3.PublicKey,
+): Promise<any> => {
+    return await program.rpc.closeMintStaked(
+        {
+            accounts: {
+                user: userWallet.publicKey,
+                poolAccount: poolAccount,
+                userAccount: userAccount,
+                mintStaked: mintStaked,
             },
             signers: [userWallet],
         });
@@ -436,7 +507,9 @@ export const unstake = async (
     configAccount: anchor.web3.PublicKey,
     userAccount: anchor.web3.PublicKey,
     unstakeFromAccount: anchor.web3.PublicKey,
-    currentMintStakedAccount: anchor.web3.PublicKey
+    currentMintStakedAccount: anchor.web3.PublicKey,
+    rewardVault?: anchor.web3.PublicKey,  // pass with rewardToAccount to claim the pending rewards
+    rewardToAccount?: anchor.web3.PublicKey,
 ): Promise<any> => {
 
     let mintStakedUuid = generateUuid();
@@ -448,6 +521,129 @@ export const unstake = async (
     )
 
     return program.rpc.unstake(
+        userMintStakedBump,
+        mintStakedUuid,
+        {
+            accounts: {
+                staker: userWallet.publicKey,
+                poolAccount: poolAccount,
+                config: configAccount,
+                authority: POOL_AUTHORITY,
+                userAccount: userAccount,
+                unstakeFromAccount: unstakeFromAccount,
+                mintStaked: userMintStakedAccount,
+                currentMintStaked: currentMintStakedAccount,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            remainingAccounts: rewardRemainingAccounts(rewardVault, rewardToAccount),
+            signers: [userWallet],
+        });
+}
+
+// stake several nfts in one instruction, the token accounts go in the remaining accounts
+export const stakeMany = async (
+    program: anchor.Program,
+    userWallet: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,
+    configAccount: anchor.web3.PublicKey,
+    userAccount: anchor.web3.PublicKey,
+    stakeFromAccounts: anchor.web3.PublicKey[],
+    currentMintStakedAccount: anchor.web3.PublicKey
+): Promise<any> => {
+
+    let mintStakedUuid = generateUuid();
+
+    let [userMintStakedAccount, userMintStakedBump] = await getMintStakedAccount(
+        poolAccount,
+        userAccount,
+        mintStakedUuid
+    )
+
+    return program.rpc.stakeMany(
+        userMintStakedBump,
+        mintStakedUuid,
+        stakeFromAccounts.length,
+        {
+            accounts: {
+                staker: userWallet.publicKey,
+                poolAccount: poolAccount,
+                config: configAccount,
+                authority: POOL_AUTHORITY,
+                userAccount: userAccount,
+                mintStaked: userMintStakedAccount,
+                currentMintStaked: currentMintStakedAccount,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            remainingAccounts: stakeFromAccounts.map((element) => ({pubkey: element, isWritable: true, isSigner: false})),
+            signers: [userWallet],
+        });
+}
+
+// unstake several nfts in one instruction, the token accounts go in the remaining accounts
+export const unstakeMany = async (
+    program: anchor.Program,
+    userWallet: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,
+    configAccount: anchor.web3.PublicKey,
+    userAccount: anchor.web3.PublicKey,
+    unstakeFromAccounts: anchor.web3.PublicKey[],
+    currentMintStakedAccount: anchor.web3.PublicKey
+): Promise<any> => {
+
+    let mintStakedUuid = generateUuid();
+
+    let [userMintStakedAccount, userMintStakedBump] = await getMintStakedAccount(
+        poolAccount,
+        userAccount,
+        mintStakedUuid
+    )
+
+    return program.rpc.unstakeMany(
+        userMintStakedBump,
+        mintStakedUuid,
+        unstakeFromAccounts.length,
+        {
+            accounts: {
+                staker: userWallet.publicKey,
+                poolAccount: poolAccount,
+                config: configAccount,
+                authority: POOL_AUTHORITY,
+                userAccount: userAccount,
+                mintStaked: userMintStakedAccount,
+                currentMintStaked: currentMintStakedAccount,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            remainingAccounts: unstakeFromAccounts.map((element) => ({pubkey: element, isWritable: true, isSigner: false})),
+            signers: [userWallet],
+        });
+}
+
+// also works while the pool is paused, rewards accrued since the last settlement are forfeited
+export const emergencyUnstake = async (
+    program: anchor.Program,
+    userWallet: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,
+    configAccount: anchor.web3.PublicKey,
+    userAccount: anchor.web3.PublicKey,
+    unstakeFromAccount: anchor.web3.PublicKey,
+    currentMintStakedAccount: anchor.web3.PublicKey,
+): Promise<any> => {
+
+    let mintStakedUuid = generateUuid();
+
+    let [userMintStakedAccount, userMintStakedBump] = await getMintStakedAccount(
+        poolAccount,
+        userAccount,
+        mintStakedUuid
+    )
+
+    return program.rpc.emergencyUnstake(
         userMintStakedBump,
         mintStakedUuid,
         {
@@ -474,6 +670,7 @@ export const claim = async (
     poolAccount: anchor.web3.PublicKey,
     rewardAccount: anchor.web3.PublicKey,
     userAccount: anchor.web3.PublicKey,
+    mintStaked: anchor.web3.PublicKey,  // current mint staked account of the user
     rewardToAccount: anchor.web3.PublicKey,
 ): Promise<any> => {
 
@@ -485,9 +682,165 @@ export const claim = async (
                 authority: POOL_AUTHORITY,
                 rewardVault: rewardAccount,
                 userAccount: userAccount,
+                mintStaked: mintStaked,
+                rewardToAccount: rewardToAccount,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            },
+            signers: [userWallet],
+        });
+}
+
+// claim into the associated token account of the user, the program creates it if needed
+export const claimToAssociatedToken = async (
+    program: anchor.Program,
+    userWallet: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,
+    rewardAccount: anchor.web3.PublicKey,
+    userAccount: anchor.web3.PublicKey,
+    mintStaked: anchor.web3.PublicKey,  // current mint staked account of the user
+): Promise<any> => {
+
+    return program.rpc.claimToAssociatedToken(
+        {
+            accounts: {
+                user: userWallet.publicKey,
+                poolAccount: poolAccount,
+                authority: POOL_AUTHORITY,
+                rewardVault: rewardAccount,
+                userAccount: userAccount,
+                mintStaked: mintStaked,
+                rewardMint: WILD_TOKEN,
+                rewardToAccount: await findAssociatedTokenAddress(userWallet.publicKey, WILD_TOKEN),
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                associatedTokenProgram: SPL_ASSOCIATED_TOKEN_ACCOUNT_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [userWallet],
+        });
+}
+
+// exact pending rewards of the user, read from the event of a simulated viewPendingRewards
+export const viewPendingRewards = async (
+    program: anchor.Program,
+    poolAccount: anchor.web3.PublicKey,
+    userAccount: anchor.web3.PublicKey,
+    mintStaked: anchor.web3.PublicKey,  // current mint staked account of the user
+): Promise<any> => {
+    let res = await program.simulate.viewPendingRewards(
+        {
+            accounts: {
+                poolAccount: poolAccount,
+                userAccount: userAccount,
+                mintStaked: mintStaked,
+            },
+        });
+    let event = res.events.find((element) => element.name == "PendingRewardsView");
+    return event == null ? null : event.data
+}
+
+// PublicKey.default as delegate disables claimFor, as recipient claimFor only pays the user token accounts
+export const setClaimDelegate = async (
+    program: anchor.Program,
+    userWallet: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,
+    userAccount: anchor.web3.PublicKey,
+    delegate: anchor.web3.PublicKey,
+    recipient: anchor.web3.PublicKey,
+): Promise<any> => {
+    return await program.rpc.setClaimDelegate(
+        delegate,
+        recipient,
+        {
+            accounts: {
+                user: userWallet.publicKey,
+                poolAccount: poolAccount,
+                userAccount: userAccount,
+            },
+            signers: [userWallet],
+        });
+}
+
+// claim by the delegate into a user token account or the recipient set by the user
+export const claimFor = async (
+    program: anchor.Program,
+    delegateWallet: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,
+    rewardAccount: anchor.web3.PublicKey,
+    userAccount: anchor.web3.PublicKey,
+    mintStaked: anchor.web3.PublicKey,  // current mint staked account of the user
+    rewardToAccount: anchor.web3.PublicKey,
+): Promise<any> => {
+
+    return program.rpc.claimFor(
+        {
+            accounts: {
+                claimer: delegateWallet.publicKey,
+                poolAccount: poolAccount,
+                authority: POOL_AUTHORITY,
+                rewardVault: rewardAccount,
+                userAccount: userAccount,
+                mintStaked: mintStaked,
                 rewardToAccount: rewardToAccount,
                 tokenProgram: TOKEN_PROGRAM_ID,
             },
+            signers: [delegateWallet],
+        });
+}
+
+// first step of the migration of a user account created with an older layout, the pool has to be migrated first
+export const beginMigrateUser = async (
+    program: anchor.Program,
+    userWallet: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,
+): Promise<any> => {
+    let [userAccount, _userBump] = await getUserAccount(poolAccount, userWallet.publicKey);
+    let [migrationAccount, migrationBump] = await getMigrationAccount(userAccount);
+    return await program.rpc.beginMigrateUser(
+        migrationBump,
+        {
+            accounts: {
+                user: userWallet.publicKey,
+                poolAccount: poolAccount,
+                userAccount: userAccount,
+                migration: migrationAccount,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [userWallet],
+        });
+}
+
+// currentMintStakedAccount is the mint staked account of the old user account, it moves to a new one
+export const migrateUser = async (
+    program: anchor.Program,
+    userWallet: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,
+    currentMintStakedAccount: anchor.web3.PublicKey,
+): Promise<any> => {
+    let [userAccount, userBump] = await getUserAccount(poolAccount, userWallet.publicKey);
+    let [migrationAccount, _migrationBump] = await getMigrationAccount(userAccount);
+    let mintStakedUuid = generateUuid();
+
+    let [userMintStakedAccount, userMintStakedBump] = await getMintStakedAccount(
+        poolAccount,
+        userAccount,
+        mintStakedUuid
+    )
+
+    return await program.rpc.migrateUser(
+        userBump,
+        userMintStakedBump,
+        mintStakedUuid,
+        {
+            accounts: {
+                user: userWallet.publicKey,
+                poolAccount: poolAccount,
+                userAccount: userAccount,
+                migration: migrationAccount,
+                currentMintStaked: currentMintStakedAccount,
+                mintStaked: userMintStakedAccount,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
             signers: [userWallet],
         });
 }
@@ -636,6 +989,7 @@ export const claim = async (
     //     poolAccount,
     //     rewardAccount,
     //     userAccount,
+    //     userState.mintStaked,
     //     rewardToAccount
     // )
 
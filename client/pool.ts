@@ -36,6 +36,8 @@ const ENV = "mainnet-beta";
 const PREFIX = "nft_staking";
 const PREFIX_USER = "nft_staking_user"
 const PREFIX_MINT = "nft_staking_mint"
+const PREFIX_MIGRATION = "nft_staking_migration"
+const PREFIX_FUNDER = "nft_staking_funder"
 
 export interface Pool {
     id: anchor.web3.PublicKey,
@@ -185,6 +187,39 @@ const getRewardAccount = async (
     );
 };
 
+// migration account holding the old data of a pool or user account during its migration
+const getMigrationAccount = async (
+    account: anchor.web3.PublicKey,
+): Promise<[anchor.web3.PublicKey, number]> => {
+    return (
+        await anchor.web3.PublicKey.findProgramAddress(
+            [Buffer.from(anchor.utils.bytes.utf8.encode(PREFIX_MIGRATION)),
+                account.toBuffer(),],
+            STAKE_PROGRAM
+        )
+    );
+};
+
+// funder account holding the cap and the amount funded of an authorized funder
+const getFunderAccount = async (
+    poolAccount: anchor.web3.PublicKey,
+    funder: anchor.web3.PublicKey,
+): Promise<[anchor.web3.PublicKey, number]> => {
+    return (
+        await anchor.web3.PublicKey.findProgramAddress(
+            [Buffer.from(anchor.utils.bytes.utf8.encode(PREFIX_FUNDER)),
+                poolAccount.toBuffer(),
+                funder.toBuffer(),],
+            STAKE_PROGRAM
+        )
+    );
+};
+
+// config space for mint groups of groupSizes mints, a u32 group count then bonus bps, u32 len and mints of each group
+export const mintGroupSpace = (groupSizes: number[]): number => {
+    return 4 + groupSizes.reduce((total, size) => total + 4 + 4 + 32 * size, 0)
+}
+
 export const initializePool = async (
     program: anchor.Program,
     authority: anchor.web3.Keypair,
@@ -197,7 +232,8 @@ export const initializePool = async (
     poolAccount: anchor.web3.PublicKey,
     poolBump: number,
     rewardAccount: anchor.web3.PublicKey,
-    rewardBump: number
+    rewardBump: number,
+    groupSpace: number = 0, // room after the mint addresses for addMintGroup, see mintGroupSpace
 ): Promise<any> => {
 
     let configSpace = (8 + // discriminator
@@ -207,6 +243,8 @@ export const initializePool = async (
             4) // u32 len for Vec<Pubkey>
         +
         (32 * numNFT)
+        +
+        groupSpace
 
     let initPoolTx = program.transaction.initializePool(
         poolBump,
@@ -268,18 +306,146 @@ export const resumePool = async (
     );
 }
 
+// users with at least minCount nfts staked earn bonusBps more on all of them, the best rule reached applies
+// the rules replace the previous ones
+export const setCountBonus = async (
+    program: anchor.Program,
+    authority: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,  // pool account public key
+    rules: { minCount: number, bonusBps: number }[],
+): Promise<any> => {
+    return await program.rpc.setCountBonus(
+        rules,
+        {
+            accounts: {
+                authority: authority.publicKey,
+                poolAccount: poolAccount,
+            },
+            signers: [authority],
+        });
+}
+
+// users staking all mints of the group earn bonusBps more on all staked nfts
+// the config needs room for the group, see the groupSpace of initializePool
+export const addMintGroup = async (
+    program: anchor.Program,
+    authority: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,  // pool account public key
+    configAccount: anchor.web3.PublicKey,  // config account public key
+    bonusBps: number,
+    mints: anchor.web3.PublicKey[],
+): Promise<any> => {
+    return await program.rpc.addMintGroup(
+        bonusBps,
+        mints,
+        {
+            accounts: {
+                authority: authority.publicKey,
+                poolAccount: poolAccount,
+                config: configAccount,
+            },
+            signers: [authority],
+        });
+}
+
+export const clearMintGroups = async (
+    program: anchor.Program,
+    authority: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,  // pool account public key
+    configAccount: anchor.web3.PublicKey,  // config account public key
+): Promise<any> => {
+    return await program.rpc.clearMintGroups(
+        {
+            accounts: {
+                authority: authority.publicKey,
+                poolAccount: poolAccount,
+                config: configAccount,
+            },
+            signers: [authority],
+        });
+}
+
+// every loyaltyStep seconds an nft stays staked adds loyaltyStepBps to its earning weight, up to loyaltyMaxBps
+// loyaltyStep 0 disables the loyalty bonus
+export const setLoyaltyRamp = async (
+    program: anchor.Program,
+    authority: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,  // pool account public key
+    loyaltyStep: number,
+    loyaltyStepBps: number,
+    loyaltyMaxBps: number,
+): Promise<any> => {
+    return await program.rpc.setLoyaltyRamp(
+        new anchor.BN(loyaltyStep),
+        loyaltyStepBps,
+        loyaltyMaxBps,
+        {
+            accounts: {
+                authority: authority.publicKey,
+                poolAccount: poolAccount,
+            },
+            signers: [authority],
+        });
+}
+
 export const authorizeFunder = async (
     program: anchor.Program,
     authority: anchor.web3.Keypair,
     poolAccount: anchor.web3.PublicKey,  // pool account public key
-    funder: anchor.web3.PublicKey,  // pool account public key
+    funder: anchor.web3.PublicKey,  // funder public key
+    cap: number,  // cap of the total amount funded
 ): Promise<any> => {
+    let [funderAccount, funderBump] = await getFunderAccount(poolAccount, funder);
     return await program.rpc.authorizeFunder(
+        funderBump,
         funder,
+        new anchor.BN(cap),
         {
             accounts: {
                 authority: authority.publicKey,
                 poolAccount: poolAccount,
+                funderAccount: funderAccount,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [authority],
+        });
+}
+
+export const setFunderCap = async (
+    program: anchor.Program,
+    authority: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,  // pool account public key
+    funder: anchor.web3.PublicKey,  // funder public key
+    cap: number,  // cap of the total amount funded
+): Promise<any> => {
+    let [funderAccount, _funderBump] = await getFunderAccount(poolAccount, funder);
+    return await program.rpc.setFunderCap(
+        new anchor.BN(cap),
+        {
+            accounts: {
+                authority: authority.publicKey,
+                poolAccount: poolAccount,
+                funderAccount: funderAccount,
+            },
+            signers: [authority],
+        });
+}
+
+// the funder account stays so the funder is still refunded on closePool
+export const deauthorizeFunder = async (
+    program: anchor.Program,
+    authority: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,  // pool account public key
+    funder: anchor.web3.PublicKey,  // funder public key
+): Promise<any> => {
+    let [funderAccount, _funderBump] = await getFunderAccount(poolAccount, funder);
+    return await program.rpc.deauthorizeFunder(
+        funder,
+        {
+            accounts: {
+                authority: authority.publicKey,
+                poolAccount: poolAccount,
+                funderAccount: funderAccount,
             },
             signers: [authority],
         });
@@ -294,15 +460,20 @@ export const fund = async (
     funderVault: anchor.web3.PublicKey,  // funder vault
     authority: anchor.web3.PublicKey, // authority
     amount: number,
+    mode: object = {reset: {}},  // {reset: {}} starts a new reward duration, {topUp: {}} keeps the rate and extends the end
 ): Promise<any> => {
+    // not used when the pool authority funds
+    let [funderAccount, _funderBump] = await getFunderAccount(poolAccount, funder.publicKey);
     return await program.rpc.fund(
         new anchor.BN(amount),
+        mode,
         {
             accounts: {
                 funder: funder.publicKey,
                 poolAccount: poolAccount,
                 rewardVault: rewardVault,
                 funderVault: funderVault,
+                funderAccount: funderAccount,
                 authority: authority,
                 tokenProgram: TOKEN_PROGRAM_ID,
                 config: configAccount
@@ -311,6 +482,52 @@ export const fund = async (
         });
 }
 
+// reward rate and reward duration end a fund of amount would give, read from the FundPreviewed event of a simulation
+export const previewFund = async (
+    program: anchor.Program,
+    poolAccount: anchor.web3.PublicKey,  // pool account public key
+    configAccount: anchor.web3.PublicKey,  // config account public key
+    amount: number,
+    mode: object = {reset: {}},
+): Promise<any> => {
+    let res = await program.simulate.previewFund(
+        new anchor.BN(amount),
+        mode,
+        {
+            accounts: {
+                poolAccount: poolAccount,
+                config: configAccount,
+            },
+        });
+    let event = res.events.find((element) => element.name == "FundPreviewed");
+    return event == null ? null : event.data
+}
+
+// open to any signer, the reward rate is kept and the reward duration end moves out like a top-up fund
+export const donate = async (
+    program: anchor.Program,
+    donor: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,  // pool account public key
+    configAccount: anchor.web3.PublicKey,  // config account public key
+    rewardVault: anchor.web3.PublicKey,  // reward vault
+    donorVault: anchor.web3.PublicKey,  // donor reward token account
+    amount: number,
+): Promise<any> => {
+    return await program.rpc.donate(
+        new anchor.BN(amount),
+        {
+            accounts: {
+                donor: donor.publicKey,
+                poolAccount: poolAccount,
+                config: configAccount,
+                rewardVault: rewardVault,
+                donorVault: donorVault,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            },
+            signers: [donor],
+        });
+}
+
 export const addMintAddresses = async (
     program: anchor.Program,
     authority: anchor.web3.Keypair,
@@ -364,7 +581,14 @@ export const closePool = async (
     refundee: anchor.web3.PublicKey,  // lamports refund to
     rewardRefundee: anchor.web3.PublicKey, // reward refund to
     rewardVault: anchor.web3.PublicKey,  // reward vault
+    funders: [anchor.web3.PublicKey, anchor.web3.PublicKey][] = [],  // funder public key and funder vault of each funder to refund
 ): Promise<any> => {
+    let remainingAccounts = [];
+    for (let [funder, funderVault] of funders) {
+        let [funderAccount, _funderBump] = await getFunderAccount(poolAccount, funder);
+        remainingAccounts.push({pubkey: funderAccount, isWritable: true, isSigner: false});
+        remainingAccounts.push({pubkey: funderVault, isWritable: true, isSigner: false});
+    }
     return await program.rpc.closePool(
         {
             accounts: {
@@ -376,6 +600,48 @@ export const closePool = async (
                 rewardVault: rewardVault,
                 tokenProgram: TOKEN_PROGRAM_ID,
             },
+            remainingAccounts: remainingAccounts,
+            signers: [authority],
+        });
+}
+
+// first step of the migration of a pool created with an older layout, migratePool follows in a later transaction
+export const beginMigratePool = async (
+    program: anchor.Program,
+    authority: anchor.web3.Keypair,
+    poolAccount: anchor.web3.PublicKey,  // pool account public key
+): Promise<any> => {
+    let [migrationAccount, migrationBump] = await getMigrationAccount(poolAccount);
+    return await program.rpc.beginMigratePool(
+        migrationBump,
+        {
+            accounts: {
+                authority: authority.publicKey,
+                poolAccount: poolAccount,
+                migration: migrationAccount,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
+            signers: [authority],
+        });
+}
+
+export const migratePool = async (
+    program: anchor.Program,
+    authority: anchor.web3.Keypair,
+    configAccount: anchor.web3.PublicKey,  // config account public key
+): Promise<any> => {
+    let [poolAccount, poolBump] = await getPoolAccount(authority.publicKey, configAccount);
+    let [migrationAccount, _migrationBump] = await getMigrationAccount(poolAccount);
+    return await program.rpc.migratePool(
+        poolBump,
+        {
+            accounts: {
+                authority: authority.publicKey,
+                poolAccount: poolAccount,
+                config: configAccount,
+                migration: migrationAccount,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            },
             signers: [authority],
         });
 }
@@ -462,7 +728,8 @@ export const closePool = async (
         poolState.rewardVault,
         WILD_VAULT,
         walletKey.publicKey,
-        5_000_000_000_000_000
+        5_000_000_000_000_000,
+        {reset: {}}
     );
     console.log("Config Account:", configAccount.toBase58())
     console.log("Pool Account:", poolAccount.toBase58())
@@ -0,0 +1,542 @@
+// random sequences of pool, funder and user instructions against two funded pools and a pool on the baseline layout
+// a step can replace one account with the account of the same role from another pool or another user,
+// an instruction that accepts the replaced account is missing a constraint
+// the staked nfts, the stake counts and the reward tokens of each pool are checked after every step
+// the baseline pool and its user only take instructions that need their state once they are migrated
+//
+// build the program first with `anchor build`, then from this directory
+// BPF_OUT_DIR=../../../target/deploy cargo hfuzz run instruction_sequence
+
+use anchor_lang::AccountDeserialize;
+use arbitrary::{Arbitrary, Unstructured};
+use honggfuzz::fuzz;
+use nft_staking::{CountBonus, FundMode, Migration, Pool};
+use nft_staking_program_test::*;
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use std::collections::HashMap;
+
+const NUM_MINT: u32 = 3;
+const REWARD_DURATION: u64 = 100_000;
+const FUND_AMOUNT: u64 = 3_000_000;
+const MAX_STEPS: usize = 24;
+
+#[derive(Debug, Arbitrary)]
+enum FuzzInstruction {
+    Pause { pool: u8 },
+    Resume { pool: u8 },
+    Fund { pool: u8, amount: u32, top_up: bool, by_funder: bool },
+    Donate { pool: u8, amount: u32 },
+    AuthorizeFunder { pool: u8, cap: u32 },
+    DeauthorizeFunder { pool: u8 },
+    SetCountBonus { pool: u8, min_count: u8, bonus_bps: u16 },
+    // bit i of `mints` picks the i-th nft mint of the pool
+    AddMintGroup { pool: u8, bonus_bps: u16, mints: u8 },
+    ClosePool { pool: u8, refund_funder: bool },
+    BeginMigratePool { pool: u8 },
+    MigratePool { pool: u8 },
+    Stake { user: u8, nft: u8 },
+    StakeMany { user: u8 },
+    Unstake { user: u8, nft: u8, claim: bool },
+    UnstakeMany { user: u8 },
+    EmergencyUnstake { user: u8, nft: u8 },
+    Claim { user: u8 },
+    ClaimToAssociatedToken { user: u8 },
+    SetClaimDelegate { user: u8, delegate: u8, to_delegate: bool },
+    ClaimFor { user: u8, delegate: u8, to_delegate: bool },
+    CloseUser { user: u8, claim: bool },
+    BeginMigrateUser { user: u8 },
+    MigrateUser { user: u8 },
+    Warp { seconds: u16 },
+}
+
+#[derive(Debug, Arbitrary)]
+struct FuzzStep {
+    instruction: FuzzInstruction,
+    // picks the account to replace and its replacement
+    substitution: Option<u16>,
+}
+
+#[derive(Clone, Copy)]
+enum Actor {
+    Authority(usize),
+    Funder(usize),
+    User(usize),
+}
+
+struct FuzzUser {
+    pool: usize,
+    user: TestUser,
+    // false until a user on the baseline layout is migrated
+    migrated: bool,
+}
+
+// funder of a pool with its own reward tokens, not authorized at the start
+struct FuzzFunder {
+    keypair: Keypair,
+    vault: Pubkey,
+}
+
+struct World {
+    pools: Vec<TestPool>,
+    funders: Vec<FuzzFunder>,
+    // false until a pool on the baseline layout is migrated
+    migrated: Vec<bool>,
+    users: Vec<FuzzUser>,
+    // reward tokens sent to each pool
+    funded: Vec<u64>,
+    // reward tokens in the vaults, funder accounts and user accounts of each pool
+    supply: Vec<u64>,
+}
+
+impl World {
+    // two funded pools with room for two mint groups, two users with the nfts of the first pool and one user of the second pool
+    // the third pool and its user are on the baseline layout
+    async fn new(context: &mut ProgramTestContext, legacy: LegacyPool) -> World {
+        let mut pools = vec![];
+        for _ in 0..2 {
+            let pool = TestPool::prepare(context, NUM_MINT).await;
+            let mint_group_space = mint_group_space(&[NUM_MINT as usize; 2]);
+            pool.initialize_with_mint_groups(context, NUM_MINT, REWARD_DURATION, mint_group_space).await.unwrap();
+            pool.add_mints(context, pool.mints.clone(), 0).await.unwrap();
+            pool.resume(context).await.unwrap();
+            pool.fund(context, FUND_AMOUNT).await.unwrap();
+            pools.push(pool);
+        }
+
+        let mut users = vec![];
+        for &(pool, ref mints) in &[(0, 0..2), (0, 2..3), (1, 0..1)] {
+            let mut user = TestUser::new(context, &pools[pool], &pools[pool].mints[mints.clone()]).await;
+            user.create(context, &pools[pool]).await.unwrap();
+            users.push(FuzzUser { pool, user, migrated: true });
+        }
+
+        let (legacy_pool, legacy_user) = legacy.into_test_accounts(context).await;
+        pools.push(legacy_pool);
+        users.push(FuzzUser {
+            pool: 2,
+            user: legacy_user,
+            migrated: false,
+        });
+
+        let mut funders = vec![];
+        for pool in &pools {
+            let keypair = create_funded_keypair(context).await;
+            let vault = create_token_account(context, &pool.reward_mint, &keypair.pubkey()).await;
+            mint_to(context, &pool.reward_mint, &vault, &pool.authority, REWARD_SUPPLY).await;
+            funders.push(FuzzFunder { keypair, vault });
+        }
+
+        let mut world = World {
+            pools,
+            funders,
+            migrated: vec![true, true, false],
+            users,
+            funded: vec![FUND_AMOUNT, FUND_AMOUNT, LegacyPool::REWARD_VAULT_AMOUNT],
+            supply: vec![],
+        };
+        for index in 0..world.pools.len() {
+            let (_, held) = world.reward_tokens(context, index).await;
+            world.supply.push(held);
+        }
+        world
+    }
+
+    fn keypair(&self, actor: Actor) -> &Keypair {
+        match actor {
+            Actor::Authority(pool) => &self.pools[pool].authority,
+            Actor::Funder(pool) => &self.funders[pool].keypair,
+            Actor::User(user) => &self.users[user].user.keypair,
+        }
+    }
+
+    async fn step(&mut self, context: &mut ProgramTestContext, step: &FuzzStep) {
+        let (mut ix, actor) = match step.instruction {
+            FuzzInstruction::Warp { seconds } => return warp_seconds(context, seconds as u64).await,
+            ref instruction => match self.instruction(context, instruction).await {
+                Some(x) => x,
+                None => return,
+            },
+        };
+
+        let substituted = match step.substitution {
+            Some(choice) => substitute(&mut ix, &self.twins(context).await, choice as usize),
+            None => None,
+        };
+        let result = process(context, &[ix], &[self.keypair(actor)]).await;
+
+        match substituted {
+            Some((original, replacement)) => assert!(
+                result.is_err(),
+                "{:?} accepted {} in place of {}",
+                step.instruction,
+                replacement,
+                original
+            ),
+            None if result.is_ok() => self.record(&step.instruction),
+            None => {}
+        }
+    }
+
+    // track the reward tokens sent to each pool and the migrated accounts after a successful step
+    fn record(&mut self, instruction: &FuzzInstruction) {
+        match *instruction {
+            FuzzInstruction::Fund { pool, amount, .. } | FuzzInstruction::Donate { pool, amount } => {
+                let index = pool as usize % self.pools.len();
+                self.funded[index] += amount as u64;
+            }
+            FuzzInstruction::MigratePool { pool } => {
+                let index = pool as usize % self.pools.len();
+                self.migrated[index] = true;
+            }
+            FuzzInstruction::MigrateUser { user } => {
+                let index = user as usize % self.users.len();
+                self.users[index].migrated = true;
+            }
+            _ => {}
+        }
+    }
+
+    // state of a pool on the current layout, None when it is not migrated or closed
+    async fn pool_state(&self, context: &mut ProgramTestContext, index: usize) -> Option<Pool> {
+        let pool = &self.pools[index];
+        if !self.migrated[index] || !account_exists(context, &pool.pool_account).await {
+            return None;
+        }
+        Some(pool.state(context).await)
+    }
+
+    // the instruction of a step and its signer, None when an account it needs is closed or not migrated
+    // every user instruction takes a new uuid, only the ones creating a mint staked account use it
+    async fn instruction(&mut self, context: &mut ProgramTestContext, instruction: &FuzzInstruction) -> Option<(Instruction, Actor)> {
+        let user = match *instruction {
+            FuzzInstruction::Warp { .. } => unreachable!(),
+            FuzzInstruction::Stake { user, .. }
+            | FuzzInstruction::StakeMany { user }
+            | FuzzInstruction::Unstake { user, .. }
+            | FuzzInstruction::UnstakeMany { user }
+            | FuzzInstruction::EmergencyUnstake { user, .. }
+            | FuzzInstruction::Claim { user }
+            | FuzzInstruction::ClaimToAssociatedToken { user }
+            | FuzzInstruction::SetClaimDelegate { user, .. }
+            | FuzzInstruction::ClaimFor { user, .. }
+            | FuzzInstruction::CloseUser { user, .. }
+            | FuzzInstruction::BeginMigrateUser { user }
+            | FuzzInstruction::MigrateUser { user } => user as usize % self.users.len(),
+            ref instruction => return self.pool_instruction(context, instruction).await,
+        };
+        let delegate = match *instruction {
+            FuzzInstruction::SetClaimDelegate { delegate, .. } | FuzzInstruction::ClaimFor { delegate, .. } => {
+                delegate as usize % self.users.len()
+            }
+            _ => user,
+        };
+        let delegate_key = self.users[delegate].user.pubkey();
+        let delegate_reward_account = self.users[delegate].user.reward_account;
+        let pool_migrated = self.migrated[self.users[user].pool];
+        let current_mint_staked = staked_accounts(context, &self.users[user]).await.map(|(x, _)| x);
+
+        let FuzzUser { pool, user: test_user, migrated } = &mut self.users[user];
+        let pool = &self.pools[*pool];
+        let staker = test_user.pubkey();
+        let uuid = test_user.next_uuid();
+        match *instruction {
+            FuzzInstruction::BeginMigrateUser { .. } => {
+                let ix = instruction::begin_migrate_user(&staker, &pool.pool_account);
+                return Some((ix, Actor::User(user)));
+            }
+            // the user account is closed between the two migration steps, the mint staked account stays
+            FuzzInstruction::MigrateUser { .. } => {
+                let ix = instruction::migrate_user(&staker, &pool.pool_account, &current_mint_staked?, &uuid);
+                return Some((ix, Actor::User(user)));
+            }
+            _ => {}
+        }
+
+        if !*migrated || !pool_migrated || !account_exists(context, &test_user.user_account).await {
+            return None;
+        }
+        let pool_state = pool.state(context).await;
+        let user_state = test_user.state(context).await;
+        let nft_accounts = &test_user.nft_accounts;
+        let nft = |index: u8| nft_accounts[index as usize % nft_accounts.len()];
+        let reward_to_account = |claim: bool| if claim { Some(&test_user.reward_account) } else { None };
+
+        let ix = match *instruction {
+            FuzzInstruction::Stake { nft: index, .. } => {
+                instruction::stake(&staker, &pool.pool_account, &pool_state, &user_state, &nft(index), &uuid)
+            }
+            FuzzInstruction::StakeMany { .. } => {
+                instruction::stake_many(&staker, &pool.pool_account, &pool_state, &user_state, nft_accounts, &uuid)
+            }
+            FuzzInstruction::Unstake { nft: index, claim, .. } => instruction::unstake(
+                &staker,
+                &pool.pool_account,
+                &pool_state,
+                &user_state,
+                &nft(index),
+                &uuid,
+                reward_to_account(claim),
+            ),
+            FuzzInstruction::UnstakeMany { .. } => {
+                instruction::unstake_many(&staker, &pool.pool_account, &pool_state, &user_state, nft_accounts, &uuid)
+            }
+            FuzzInstruction::EmergencyUnstake { nft: index, .. } => {
+                instruction::emergency_unstake(&staker, &pool.pool_account, &pool_state, &user_state, &nft(index), &uuid)
+            }
+            FuzzInstruction::Claim { .. } => {
+                instruction::claim(&staker, &pool.pool_account, &pool_state, &user_state, &test_user.reward_account)
+            }
+            FuzzInstruction::ClaimToAssociatedToken { .. } => {
+                instruction::claim_to_associated_token(&staker, &pool.pool_account, &pool_state, &user_state)
+            }
+            // the recipient is the reward token account of the delegate or none
+            FuzzInstruction::SetClaimDelegate { to_delegate, .. } => {
+                let recipient = if to_delegate { delegate_reward_account } else { Pubkey::default() };
+                instruction::set_claim_delegate(&staker, &pool.pool_account, &delegate_key, &recipient)
+            }
+            // signed by the delegate, paid to the reward token account of the delegate or of the user
+            FuzzInstruction::ClaimFor { to_delegate, .. } => {
+                let reward_to_account = if to_delegate { delegate_reward_account } else { test_user.reward_account };
+                let ix = instruction::claim_for(&delegate_key, &staker, &pool.pool_account, &pool_state, &user_state, &reward_to_account);
+                return Some((ix, Actor::User(delegate)));
+            }
+            FuzzInstruction::CloseUser { claim, .. } => {
+                instruction::close_user(&staker, &pool.pool_account, &pool_state, &user_state, reward_to_account(claim))
+            }
+            _ => unreachable!(),
+        };
+        Some((ix, Actor::User(user)))
+    }
+
+    // the instruction of a pool step, signed by the authority or the funder of the pool
+    async fn pool_instruction(&self, context: &mut ProgramTestContext, instruction: &FuzzInstruction) -> Option<(Instruction, Actor)> {
+        let index = match *instruction {
+            FuzzInstruction::Pause { pool }
+            | FuzzInstruction::Resume { pool }
+            | FuzzInstruction::Fund { pool, .. }
+            | FuzzInstruction::Donate { pool, .. }
+            | FuzzInstruction::AuthorizeFunder { pool, .. }
+            | FuzzInstruction::DeauthorizeFunder { pool }
+            | FuzzInstruction::SetCountBonus { pool, .. }
+            | FuzzInstruction::AddMintGroup { pool, .. }
+            | FuzzInstruction::ClosePool { pool, .. }
+            | FuzzInstruction::BeginMigratePool { pool }
+            | FuzzInstruction::MigratePool { pool } => pool as usize % self.pools.len(),
+            _ => unreachable!(),
+        };
+        let pool = &self.pools[index];
+        let authority = pool.authority.pubkey();
+        let funder = self.funders[index].keypair.pubkey();
+        let funder_vault = self.funders[index].vault;
+
+        let ix = match *instruction {
+            FuzzInstruction::Pause { .. } => instruction::pause(&authority, &pool.pool_account),
+            FuzzInstruction::Resume { .. } => instruction::resume(&authority, &pool.pool_account),
+            FuzzInstruction::AuthorizeFunder { cap, .. } => instruction::authorize_funder(&authority, &pool.pool_account, &funder, cap as u64),
+            FuzzInstruction::DeauthorizeFunder { .. } => instruction::deauthorize_funder(&authority, &pool.pool_account, &funder),
+            FuzzInstruction::SetCountBonus { min_count, bonus_bps, .. } => {
+                let rules = vec![CountBonus {
+                    min_count: min_count as u32,
+                    bonus_bps: bonus_bps as u32,
+                }];
+                instruction::set_count_bonus(&authority, &pool.pool_account, rules)
+            }
+            FuzzInstruction::AddMintGroup { bonus_bps, mints, .. } => {
+                let mints = pool.mints.iter().enumerate().filter(|(i, _)| mints >> i & 1 == 1).map(|(_, x)| *x).collect();
+                instruction::add_mint_group(&authority, &pool.pool_account, &pool.config, bonus_bps as u32, mints)
+            }
+            FuzzInstruction::BeginMigratePool { .. } => instruction::begin_migrate_pool(&authority, &pool.pool_account),
+            FuzzInstruction::MigratePool { .. } => instruction::migrate_pool(&authority, &pool.config),
+            FuzzInstruction::Fund { amount, top_up, by_funder, .. } => {
+                let state = self.pool_state(context, index).await?;
+                let mode = if top_up { FundMode::TopUp } else { FundMode::Reset };
+                if by_funder {
+                    let ix = instruction::fund(&funder, &pool.pool_account, &state, &funder_vault, amount as u64, mode);
+                    return Some((ix, Actor::Funder(index)));
+                }
+                instruction::fund(&authority, &pool.pool_account, &state, &pool.funder_vault, amount as u64, mode)
+            }
+            // anyone can donate, the funder does it whether it is authorized or not
+            FuzzInstruction::Donate { amount, .. } => {
+                let state = self.pool_state(context, index).await?;
+                let ix = instruction::donate(&funder, &pool.pool_account, &state, &funder_vault, amount as u64);
+                return Some((ix, Actor::Funder(index)));
+            }
+            // the refundee is a new address, any account is accepted there
+            FuzzInstruction::ClosePool { refund_funder, .. } => {
+                let state = self.pool_state(context, index).await?;
+                let funders = if refund_funder { vec![(funder, funder_vault)] } else { vec![] };
+                instruction::close_pool(&authority, &pool.pool_account, &state, &Pubkey::new_unique(), &pool.funder_vault, &funders)
+            }
+            _ => unreachable!(),
+        };
+        Some((ix, Actor::Authority(index)))
+    }
+
+    // accounts of the same role grouped by pool or user, an account can be replaced by any account of another group
+    async fn twins(&self, context: &mut ProgramTestContext) -> HashMap<Pubkey, Vec<Pubkey>> {
+        let per_pool = |f: fn(&TestPool) -> Pubkey| self.pools.iter().map(|x| vec![f(x)]).collect::<Vec<_>>();
+        let mut roles = vec![
+            per_pool(|x| x.pool_account),
+            per_pool(|x| x.config),
+            per_pool(|x| x.authority.pubkey()),
+            per_pool(|x| x.reward_mint),
+            per_pool(|x| x.reward_vault),
+            self.pools.iter().zip(&self.funders).map(|(x, funder)| vec![x.funder_vault, funder.vault]).collect(),
+            self.pools
+                .iter()
+                .zip(&self.funders)
+                .map(|(x, funder)| vec![pda::funder_address(&x.pool_account, &funder.keypair.pubkey()).0])
+                .collect(),
+            self.users.iter().map(|x| vec![x.user.user_account]).collect(),
+            self.users.iter().map(|x| x.user.nft_accounts.clone()).collect(),
+        ];
+
+        let mut mint_staked = vec![];
+        for user in &self.users {
+            if let Some((address, _)) = staked_accounts(context, user).await {
+                mint_staked.push(vec![address]);
+            }
+        }
+        roles.push(mint_staked);
+
+        let mut twins: HashMap<Pubkey, Vec<Pubkey>> = HashMap::new();
+        for groups in &roles {
+            for (i, group) in groups.iter().enumerate() {
+                let others: Vec<Pubkey> = groups.iter().enumerate().filter(|(j, _)| *j != i).flat_map(|(_, x)| x.clone()).collect();
+                if others.is_empty() {
+                    continue;
+                }
+                for account in group {
+                    twins.entry(*account).or_default().extend(&others);
+                }
+            }
+        }
+        twins
+    }
+
+    // reward tokens in the reward vault and the user accounts of a pool, and those plus the funder accounts
+    async fn reward_tokens(&self, context: &mut ProgramTestContext, index: usize) -> (u64, u64) {
+        let pool = &self.pools[index];
+        let mut rewards = 0;
+        if account_exists(context, &pool.reward_vault).await {
+            rewards += token_balance(context, &pool.reward_vault).await;
+        }
+        for FuzzUser { user, .. } in self.users.iter().filter(|x| x.pool == index) {
+            rewards += token_balance(context, &user.reward_account).await;
+            let associated_token = pda::associated_token_address(&user.pubkey(), &pool.reward_mint);
+            if account_exists(context, &associated_token).await {
+                rewards += token_balance(context, &associated_token).await;
+            }
+        }
+        let held = rewards + token_balance(context, &pool.funder_vault).await + token_balance(context, &self.funders[index].vault).await;
+        (rewards, held)
+    }
+
+    async fn check_invariants(&self, context: &mut ProgramTestContext) {
+        for (index, pool) in self.pools.iter().enumerate() {
+            let mut staked = 0;
+            for fuzz_user in self.users.iter().filter(|x| x.pool == index) {
+                let user = &fuzz_user.user;
+                let mut mint_accounts = vec![];
+                if let Some((_, accounts)) = staked_accounts(context, fuzz_user).await {
+                    mint_accounts = accounts;
+                    staked += mint_accounts.len() as u32;
+                }
+                for nft_account in &user.nft_accounts {
+                    let owner = get_token_account(context, nft_account).await.owner;
+                    let expected = if mint_accounts.contains(nft_account) { pool.pool_account } else { user.pubkey() };
+                    assert_eq!(owner, expected, "owner of nft account {}", nft_account);
+                }
+            }
+
+            let (rewards, held) = self.reward_tokens(context, index).await;
+            assert_eq!(held, self.supply[index], "reward tokens held for pool {}", index);
+            if !account_exists(context, &pool.pool_account).await {
+                // between begin_migrate_pool and migrate_pool the pool data is in the migration account
+                let (migration, _) = pda::migration_address(&pool.pool_account);
+                if !account_exists(context, &migration).await {
+                    assert!(!account_exists(context, &pool.reward_vault).await, "reward vault of closed pool {}", index);
+                }
+                continue;
+            }
+            if self.migrated[index] {
+                assert_eq!(pool.state(context).await.token_stake_count, staked, "staked nfts of pool {}", index);
+            }
+            assert_eq!(rewards, self.funded[index], "reward tokens of pool {}", index);
+        }
+    }
+}
+
+// the mint staked account of a user and its staked nft accounts, on either layout, None when the user account is closed
+// a user on the baseline layout is read from its migration account between the two migration steps
+// the stake count of the user account has to match the staked nft accounts
+async fn staked_accounts(context: &mut ProgramTestContext, user: &FuzzUser) -> Option<(Pubkey, Vec<Pubkey>)> {
+    let address = &user.user.user_account;
+    let (migration, _) = pda::migration_address(address);
+    let (mint_staked, count, mint_accounts) = if account_exists(context, address).await && user.migrated {
+        let state = get_user(context, address).await;
+        let mint_accounts = get_mint_staked(context, &state.mint_staked).await.mint_accounts;
+        (state.mint_staked, state.mint_staked_count, mint_accounts)
+    } else if !user.migrated {
+        let state: baseline::User = match account_exists(context, address).await {
+            true => get_account(context, address).await,
+            false if account_exists(context, &migration).await => {
+                let data = get_account::<Migration>(context, &migration).await.data;
+                baseline::User::try_deserialize(&mut &data[..]).unwrap()
+            }
+            false => return None,
+        };
+        let mint_accounts = get_account::<baseline::MintStaked>(context, &state.mint_staked).await.mint_accounts;
+        (state.mint_staked, state.mint_staked_count, mint_accounts)
+    } else {
+        return None;
+    };
+    assert_eq!(mint_accounts.len(), count as usize, "staked nfts of user {}", address);
+    Some((mint_staked, mint_accounts))
+}
+
+// replace one non signer account that has twins, returns the original and the replacement
+fn substitute(ix: &mut Instruction, twins: &HashMap<Pubkey, Vec<Pubkey>>, choice: usize) -> Option<(Pubkey, Pubkey)> {
+    let candidates: Vec<usize> = ix
+        .accounts
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| !x.is_signer && twins.contains_key(&x.pubkey))
+        .map(|(i, _)| i)
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let meta = &mut ix.accounts[candidates[choice % candidates.len()]];
+    let options = &twins[&meta.pubkey];
+    let original = meta.pubkey;
+    meta.pubkey = options[choice / candidates.len() % options.len()];
+    Some((original, meta.pubkey))
+}
+
+async fn run(steps: &[FuzzStep]) {
+    let mut program_test = program_test();
+    let legacy = LegacyPool::add_to(&mut program_test, false);
+    let mut context = program_test.start_with_context().await;
+    let mut world = World::new(&mut context, legacy).await;
+    for step in steps.iter().take(MAX_STEPS) {
+        world.step(&mut context, step).await;
+        world.check_invariants(&mut context).await;
+    }
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    loop {
+        fuzz!(|data: &[u8]| {
+            if let Ok(steps) = Vec::<FuzzStep>::arbitrary_take_rest(Unstructured::new(data)) {
+                runtime.block_on(run(&steps));
+            }
+        });
+    }
+}
@@ -0,0 +1,73 @@
+// accounts as the program wrote them before account versioning, copied from the first release
+// the names match the current accounts so the discriminators do too
+
+use anchor_lang::prelude::*;
+
+pub const POOL_SIZE: usize = 8 + // discriminator
+    1 + // is_initialized
+    32 + // authority
+    1 + // paused
+    32 + // config
+    32 + // reward_mint
+    32 + // reward_vault
+    8 + // last_update_time
+    16 + // reward_per_token
+    8 + // reward_duration
+    8 + // reward_duration_end
+    4 + // token_stake_count
+    4 + // user_count
+    4 + 32 * 5; // funders
+
+#[account]
+#[derive(Default)]
+pub struct Pool {
+    pub is_initialized: bool,
+    pub authority: Pubkey,
+    pub paused: bool,
+    pub config: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub last_update_time: u64,
+    pub reward_rate_per_token: u128,
+    pub reward_duration: u64,
+    pub reward_duration_end: u64,
+    pub token_stake_count: u32,
+    pub user_count: u32,
+    pub funders: [Pubkey; 5],
+}
+
+pub const USER_SIZE: usize = 8 + // discriminator
+    32 + // pool
+    32 + // user
+    8 + // reward_per_token_complete
+    8 + // reward_per_token_pending
+    4 + // mint_staked_count
+    4 + 6 + // uuid + u32 le
+    32 + // mint_staked
+    8; // last update time
+
+#[account]
+#[derive(Default)]
+pub struct User {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub reward_earned_claimed: u64,
+    pub reward_earned_pending: u64,
+    pub mint_staked_count: u32,
+    pub uuid: String,
+    pub mint_staked: Pubkey,
+    pub last_update_time: u64,
+}
+
+pub const MINT_STAKED_SIZE_START: usize = 8 + // discriminator
+    32 + // pool
+    32 + // user_account
+    4; // u32 len for Vec<Pubkey>
+
+#[account]
+#[derive(Default)]
+pub struct MintStaked {
+    pub pool: Pubkey,
+    pub user_account: Pubkey,
+    pub mint_accounts: Vec<Pubkey>,
+}
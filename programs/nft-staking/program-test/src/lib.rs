@@ -0,0 +1,594 @@
+// shared setup for the solana-program-test suites in tests/ and the fuzz target
+// instructions and addresses come from nft_staking_sdk
+
+use anchor_lang::prelude::ProgramError;
+use anchor_lang::{AccountDeserialize, AccountSerialize};
+use nft_staking::{Config, FundMode, Funder, MintStaked, Pool, User, CONFIG_SIZE_START};
+pub use nft_staking_sdk::state::{config_space, mint_group_space};
+pub use nft_staking_sdk::{instruction, pda};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::program_option::COption;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::{system_instruction, system_program};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_sdk::transport::TransportError;
+use std::fmt::Debug;
+
+pub mod baseline;
+
+// baseline accounts are owned by the program
+use nft_staking::ID;
+
+pub const REWARD_SUPPLY: u64 = 1_000_000_000;
+pub const CONFIG_SEED: &str = "config";
+const USER_LAMPORTS: u64 = 10_000_000_000;
+// slots per warp step, the bank timestamp only moves forward a bit with each warp
+const WARP_SLOTS: u64 = 2000;
+
+// with BPF_OUT_DIR set the program is loaded from nft_staking.so, otherwise it runs natively through processor!
+// spl-token and the associated token program are bundled
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new("nft_staking", nft_staking::ID, processor!(nft_staking::entry))
+}
+
+// send the instructions in one transaction paid by the context payer
+// every transaction goes to a new slot so identical transactions get a fresh blockhash
+pub async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransportError> {
+    let slot = context.banks_client.get_clock().await?.slot;
+    context.warp_to_slot(slot + 2).unwrap();
+    context.last_blockhash = context.banks_client.get_recent_blockhash().await?;
+
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+// move the bank clock forward by at least `seconds`
+pub async fn warp_seconds(context: &mut ProgramTestContext, seconds: u64) {
+    let target = now(context).await + seconds;
+    loop {
+        let clock = context.banks_client.get_clock().await.unwrap();
+        if clock.unix_timestamp as u64 >= target {
+            break;
+        }
+        context.warp_to_slot(clock.slot + WARP_SLOTS).unwrap();
+    }
+}
+
+pub async fn now(context: &mut ProgramTestContext) -> u64 {
+    context.banks_client.get_clock().await.unwrap().unix_timestamp as u64
+}
+
+pub fn assert_error<E: Into<ProgramError> + Debug + Copy>(result: Result<(), TransportError>, error: E) {
+    let code = match error.into() {
+        ProgramError::Custom(code) => code,
+        other => panic!("{:?} is not a custom error: {:?}", error, other),
+    };
+    match result {
+        Err(TransportError::TransactionError(TransactionError::InstructionError(_, InstructionError::Custom(x)))) => {
+            assert_eq!(x, code, "expected {:?}", error)
+        }
+        other => panic!("expected {:?}, got {:?}", error, other),
+    }
+}
+
+// rejected by an anchor account constraint
+pub fn assert_constraint_error(result: Result<(), TransportError>) {
+    assert_error(result, anchor_lang::__private::ErrorCode::ConstraintRaw);
+}
+
+pub async fn account_exists(context: &mut ProgramTestContext, address: &Pubkey) -> bool {
+    context.banks_client.get_account(*address).await.unwrap().is_some()
+}
+
+async fn account_data(context: &mut ProgramTestContext, address: &Pubkey) -> Vec<u8> {
+    context.banks_client.get_account(*address).await.unwrap().expect("account not found").data
+}
+
+// any account type, the baseline layouts included
+pub async fn get_account<T: AccountDeserialize>(context: &mut ProgramTestContext, address: &Pubkey) -> T {
+    T::try_deserialize(&mut &account_data(context, address).await[..]).unwrap()
+}
+
+pub async fn get_pool(context: &mut ProgramTestContext, address: &Pubkey) -> Pool {
+    Pool::try_deserialize(&mut &account_data(context, address).await[..]).unwrap()
+}
+
+pub async fn get_user(context: &mut ProgramTestContext, address: &Pubkey) -> User {
+    User::try_deserialize(&mut &account_data(context, address).await[..]).unwrap()
+}
+
+pub async fn get_mint_staked(context: &mut ProgramTestContext, address: &Pubkey) -> MintStaked {
+    MintStaked::try_deserialize(&mut &account_data(context, address).await[..]).unwrap()
+}
+
+pub async fn get_funder(context: &mut ProgramTestContext, address: &Pubkey) -> Funder {
+    Funder::try_deserialize(&mut &account_data(context, address).await[..]).unwrap()
+}
+
+pub async fn get_token_account(context: &mut ProgramTestContext, address: &Pubkey) -> spl_token::state::Account {
+    spl_token::state::Account::unpack(&account_data(context, address).await).unwrap()
+}
+
+pub async fn token_balance(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    get_token_account(context, address).await.amount
+}
+
+pub async fn create_funded_keypair(context: &mut ProgramTestContext) -> Keypair {
+    let keypair = Keypair::new();
+    process(
+        context,
+        &[system_instruction::transfer(&context.payer.pubkey(), &keypair.pubkey(), USER_LAMPORTS)],
+        &[],
+    )
+    .await
+    .unwrap();
+    keypair
+}
+
+pub async fn create_mint(context: &mut ProgramTestContext, authority: &Pubkey, decimals: u8) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    process(
+        context,
+        &[
+            system_instruction::create_account(
+                &context.payer.pubkey(),
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), authority, None, decimals).unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(context: &mut ProgramTestContext, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    let account = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    process(
+        context,
+        &[
+            system_instruction::create_account(
+                &context.payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(&spl_token::id(), &account.pubkey(), mint, owner).unwrap(),
+        ],
+        &[&account],
+    )
+    .await
+    .unwrap();
+    account.pubkey()
+}
+
+pub async fn mint_to(context: &mut ProgramTestContext, mint: &Pubkey, account: &Pubkey, authority: &Keypair, amount: u64) {
+    process(
+        context,
+        &[spl_token::instruction::mint_to(&spl_token::id(), mint, account, &authority.pubkey(), &[], amount).unwrap()],
+        &[authority],
+    )
+    .await
+    .unwrap();
+}
+
+// pool authority with the reward mint, a funder vault holding the whole reward supply and the stakable nft mints
+pub struct TestPool {
+    pub authority: Keypair,
+    pub config: Pubkey,
+    pub pool_account: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub funder_vault: Pubkey,
+    pub mints: Vec<Pubkey>,
+}
+
+impl TestPool {
+    // everything needed to initialize a pool with `num_mint` nft mints, the pool itself is not created
+    pub async fn prepare(context: &mut ProgramTestContext, num_mint: u32) -> TestPool {
+        let authority = create_funded_keypair(context).await;
+        let reward_mint = create_mint(context, &authority.pubkey(), 6).await;
+        let funder_vault = create_token_account(context, &reward_mint, &authority.pubkey()).await;
+        mint_to(context, &reward_mint, &funder_vault, &authority, REWARD_SUPPLY).await;
+
+        let mut mints = vec![];
+        for _ in 0..num_mint {
+            mints.push(create_mint(context, &authority.pubkey(), 0).await);
+        }
+
+        let config = pda::config_address(&authority.pubkey(), CONFIG_SEED).unwrap();
+        let (pool_account, _) = pda::pool_address(&authority.pubkey(), &config);
+        let (reward_vault, _) = pda::reward_vault_address(&pool_account, &authority.pubkey(), &reward_mint);
+        TestPool {
+            authority,
+            config,
+            pool_account,
+            reward_mint,
+            reward_vault,
+            funder_vault,
+            mints,
+        }
+    }
+
+    // initialized pool with all nft mints added, still paused
+    pub async fn new(context: &mut ProgramTestContext, num_mint: u32, reward_duration: u64) -> TestPool {
+        let pool = TestPool::prepare(context, num_mint).await;
+        pool.initialize(context, num_mint, reward_duration).await.unwrap();
+        pool.add_mints(context, pool.mints.clone(), 0).await.unwrap();
+        pool
+    }
+
+    // initialized, resumed and funded with `amount`
+    pub async fn new_funded(context: &mut ProgramTestContext, num_mint: u32, reward_duration: u64, amount: u64) -> TestPool {
+        let pool = TestPool::new(context, num_mint, reward_duration).await;
+        pool.resume(context).await.unwrap();
+        pool.fund(context, amount).await.unwrap();
+        pool
+    }
+
+    pub async fn initialize(&self, context: &mut ProgramTestContext, num_mint: u32, reward_duration: u64) -> Result<(), TransportError> {
+        self.initialize_with_mint_groups(context, num_mint, reward_duration, 0).await
+    }
+
+    // config with `mint_group_space` bytes for mint groups
+    pub async fn initialize_with_mint_groups(
+        &self,
+        context: &mut ProgramTestContext,
+        num_mint: u32,
+        reward_duration: u64,
+        mint_group_space: usize,
+    ) -> Result<(), TransportError> {
+        let rent = context.banks_client.get_rent().await.unwrap();
+        let authority = self.authority.pubkey();
+        process(
+            context,
+            &[
+                instruction::create_config_account_with_mint_groups(
+                    &authority,
+                    CONFIG_SEED,
+                    rent.minimum_balance(config_space(num_mint) + mint_group_space),
+                    num_mint,
+                    mint_group_space,
+                ),
+                instruction::initialize_pool(&authority, &self.config, &self.reward_mint, "pool", num_mint, reward_duration),
+            ],
+            &[&self.authority],
+        )
+        .await
+    }
+
+    pub async fn add_mints(&self, context: &mut ProgramTestContext, mints: Vec<Pubkey>, index: u32) -> Result<(), TransportError> {
+        let ix = instruction::add_mint_addresses(&self.authority.pubkey(), &self.pool_account, &self.config, mints, index);
+        process(context, &[ix], &[&self.authority]).await
+    }
+
+    pub async fn pause(&self, context: &mut ProgramTestContext) -> Result<(), TransportError> {
+        let ix = instruction::pause(&self.authority.pubkey(), &self.pool_account);
+        process(context, &[ix], &[&self.authority]).await
+    }
+
+    pub async fn resume(&self, context: &mut ProgramTestContext) -> Result<(), TransportError> {
+        let ix = instruction::resume(&self.authority.pubkey(), &self.pool_account);
+        process(context, &[ix], &[&self.authority]).await
+    }
+
+    pub async fn fund(&self, context: &mut ProgramTestContext, amount: u64) -> Result<(), TransportError> {
+        let pool = self.state(context).await;
+        let ix = instruction::fund(&self.authority.pubkey(), &self.pool_account, &pool, &self.funder_vault, amount, FundMode::Reset);
+        process(context, &[ix], &[&self.authority]).await
+    }
+
+    pub async fn state(&self, context: &mut ProgramTestContext) -> Pool {
+        get_pool(context, &self.pool_account).await
+    }
+}
+
+// staker holding one nft of each given mint and an empty reward token account
+pub struct TestUser {
+    pub keypair: Keypair,
+    pub user_account: Pubkey,
+    pub nft_accounts: Vec<Pubkey>,
+    pub reward_account: Pubkey,
+    uuid_count: u32,
+}
+
+impl TestUser {
+    pub async fn new(context: &mut ProgramTestContext, pool: &TestPool, mints: &[Pubkey]) -> TestUser {
+        let keypair = create_funded_keypair(context).await;
+        let mut nft_accounts = vec![];
+        for mint in mints {
+            let nft_account = create_token_account(context, mint, &keypair.pubkey()).await;
+            mint_to(context, mint, &nft_account, &pool.authority, 1).await;
+            nft_accounts.push(nft_account);
+        }
+        let reward_account = create_token_account(context, &pool.reward_mint, &keypair.pubkey()).await;
+        let (user_account, _) = pda::user_address(&pool.pool_account, &keypair.pubkey());
+        TestUser {
+            keypair,
+            user_account,
+            nft_accounts,
+            reward_account,
+            uuid_count: 0,
+        }
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    // every mint staked account needs a new uuid
+    pub fn next_uuid(&mut self) -> String {
+        self.uuid_count += 1;
+        format!("uuid{}", self.uuid_count)
+    }
+
+    pub async fn state(&self, context: &mut ProgramTestContext) -> User {
+        get_user(context, &self.user_account).await
+    }
+
+    pub async fn create(&mut self, context: &mut ProgramTestContext, pool: &TestPool) -> Result<(), TransportError> {
+        let uuid = self.next_uuid();
+        let ix = instruction::create_user(&self.pubkey(), &pool.pool_account, &uuid);
+        process(context, &[ix], &[&self.keypair]).await
+    }
+
+    pub async fn stake(&mut self, context: &mut ProgramTestContext, pool: &TestPool, nft_account: &Pubkey) -> Result<(), TransportError> {
+        let pool_state = pool.state(context).await;
+        let user_state = self.state(context).await;
+        let uuid = self.next_uuid();
+        let ix = instruction::stake(&self.pubkey(), &pool.pool_account, &pool_state, &user_state, nft_account, &uuid);
+        process(context, &[ix], &[&self.keypair]).await
+    }
+
+    pub async fn stake_many(&mut self, context: &mut ProgramTestContext, pool: &TestPool, nft_accounts: &[Pubkey]) -> Result<(), TransportError> {
+        let pool_state = pool.state(context).await;
+        let user_state = self.state(context).await;
+        let uuid = self.next_uuid();
+        let ix = instruction::stake_many(&self.pubkey(), &pool.pool_account, &pool_state, &user_state, nft_accounts, &uuid);
+        process(context, &[ix], &[&self.keypair]).await
+    }
+
+    pub async fn unstake(
+        &mut self,
+        context: &mut ProgramTestContext,
+        pool: &TestPool,
+        nft_account: &Pubkey,
+        reward_to_account: Option<&Pubkey>,
+    ) -> Result<(), TransportError> {
+        let pool_state = pool.state(context).await;
+        let user_state = self.state(context).await;
+        let uuid = self.next_uuid();
+        let ix = instruction::unstake(&self.pubkey(), &pool.pool_account, &pool_state, &user_state, nft_account, &uuid, reward_to_account);
+        process(context, &[ix], &[&self.keypair]).await
+    }
+
+    pub async fn unstake_many(&mut self, context: &mut ProgramTestContext, pool: &TestPool, nft_accounts: &[Pubkey]) -> Result<(), TransportError> {
+        let pool_state = pool.state(context).await;
+        let user_state = self.state(context).await;
+        let uuid = self.next_uuid();
+        let ix = instruction::unstake_many(&self.pubkey(), &pool.pool_account, &pool_state, &user_state, nft_accounts, &uuid);
+        process(context, &[ix], &[&self.keypair]).await
+    }
+
+    pub async fn claim(&self, context: &mut ProgramTestContext, pool: &TestPool) -> Result<(), TransportError> {
+        let pool_state = pool.state(context).await;
+        let user_state = self.state(context).await;
+        let ix = instruction::claim(&self.pubkey(), &pool.pool_account, &pool_state, &user_state, &self.reward_account);
+        process(context, &[ix], &[&self.keypair]).await
+    }
+
+    pub async fn close(&self, context: &mut ProgramTestContext, pool: &TestPool, reward_to_account: Option<&Pubkey>) -> Result<(), TransportError> {
+        let pool_state = pool.state(context).await;
+        let user_state = self.state(context).await;
+        let ix = instruction::close_user(&self.pubkey(), &pool.pool_account, &pool_state, &user_state, reward_to_account);
+        process(context, &[ix], &[&self.keypair]).await
+    }
+}
+
+// a pool with one user staking one nft, written with the baseline layout from before account versioning
+// the program can't create these accounts anymore, they are added to the genesis of the program test
+pub struct LegacyPool {
+    pub authority: Keypair,
+    pub config: Pubkey,
+    pub pool_account: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub pool: baseline::Pool,
+    pub user: Keypair,
+    pub user_account: Pubkey,
+    pub user_state: baseline::User,
+    pub mint_staked: Pubkey,
+    pub nft_account: Pubkey,
+}
+
+impl LegacyPool {
+    pub const REWARD_VAULT_AMOUNT: u64 = 1_000_000;
+
+    pub fn add_to(program_test: &mut ProgramTest, paused: bool) -> LegacyPool {
+        let authority = Keypair::new();
+        let user = Keypair::new();
+        add_account(program_test, &authority.pubkey(), &system_program::id(), vec![]);
+        add_account(program_test, &user.pubkey(), &system_program::id(), vec![]);
+
+        let reward_mint = Pubkey::new_unique();
+        let nft_mint = Pubkey::new_unique();
+        add_mint(program_test, &reward_mint, &authority.pubkey(), REWARD_SUPPLY, 6);
+        add_mint(program_test, &nft_mint, &authority.pubkey(), 1, 0);
+
+        let config = pda::config_address(&authority.pubkey(), CONFIG_SEED).unwrap();
+        let config_state = Config {
+            authority: authority.pubkey(),
+            uuid: "pool".to_string(),
+            num_mint: 1,
+        };
+        let mut config_data = serialize(&config_state);
+        config_data.resize(config_space(1), 0);
+        config_data[CONFIG_SIZE_START..CONFIG_SIZE_START + 4].copy_from_slice(&1u32.to_le_bytes());
+        config_data[CONFIG_SIZE_START + 4..].copy_from_slice(nft_mint.as_ref());
+        add_account(program_test, &config, &nft_staking::ID, config_data);
+
+        let (pool_account, _) = pda::pool_address(&authority.pubkey(), &config);
+        let (reward_vault, _) = pda::reward_vault_address(&pool_account, &authority.pubkey(), &reward_mint);
+        add_token_account(program_test, &reward_vault, &reward_mint, &pool_account, Self::REWARD_VAULT_AMOUNT);
+        let nft_account = Pubkey::new_unique();
+        add_token_account(program_test, &nft_account, &nft_mint, &pool_account, 1);
+
+        // every field set so a wrong byte mapping shows up after migration
+        let pool = baseline::Pool {
+            is_initialized: true,
+            authority: authority.pubkey(),
+            paused,
+            config,
+            reward_mint,
+            reward_vault,
+            last_update_time: 1_600_000_000,
+            // one token per nft second
+            reward_rate_per_token: u64::MAX as u128,
+            reward_duration: 100_000,
+            // far out so a resume extends it
+            reward_duration_end: u32::MAX as u64,
+            token_stake_count: 1,
+            user_count: 1,
+            funders: [Pubkey::new_unique(), Pubkey::default(), Pubkey::new_unique(), Pubkey::default(), Pubkey::default()],
+        };
+        add_account(program_test, &pool_account, &nft_staking::ID, baseline_layout(&pool, baseline::POOL_SIZE));
+
+        // the baseline sized user accounts for a 6 character uuid
+        let (user_account, _) = pda::user_address(&pool_account, &user.pubkey());
+        let (mint_staked, _) = pda::mint_staked_address(&pool_account, &user_account, "uuid00");
+        let user_state = baseline::User {
+            pool: pool_account,
+            user: user.pubkey(),
+            reward_earned_claimed: 3,
+            reward_earned_pending: 5,
+            mint_staked_count: 1,
+            uuid: "uuid00".to_string(),
+            mint_staked,
+            last_update_time: 1_600_000_007,
+        };
+        add_account(program_test, &user_account, &nft_staking::ID, baseline_layout(&user_state, baseline::USER_SIZE));
+
+        let mint_staked_state = baseline::MintStaked {
+            pool: pool_account,
+            user_account,
+            mint_accounts: vec![nft_account],
+        };
+        let mint_staked_size = baseline::MINT_STAKED_SIZE_START + 32;
+        add_account(program_test, &mint_staked, &nft_staking::ID, baseline_layout(&mint_staked_state, mint_staked_size));
+
+        LegacyPool {
+            authority,
+            config,
+            pool_account,
+            reward_mint,
+            reward_vault,
+            pool,
+            user,
+            user_account,
+            user_state,
+            mint_staked,
+            nft_account,
+        }
+    }
+
+    // the pool and its user as test accounts, the authority gets a funder vault and the user a reward token account
+    pub async fn into_test_accounts(self, context: &mut ProgramTestContext) -> (TestPool, TestUser) {
+        let funder_vault = create_token_account(context, &self.reward_mint, &self.authority.pubkey()).await;
+        mint_to(context, &self.reward_mint, &funder_vault, &self.authority, REWARD_SUPPLY).await;
+        let nft_mint = get_token_account(context, &self.nft_account).await.mint;
+        let reward_account = create_token_account(context, &self.reward_mint, &self.user.pubkey()).await;
+
+        let pool = TestPool {
+            authority: self.authority,
+            config: self.config,
+            pool_account: self.pool_account,
+            reward_mint: self.reward_mint,
+            reward_vault: self.reward_vault,
+            funder_vault,
+            mints: vec![nft_mint],
+        };
+        let user = TestUser {
+            keypair: self.user,
+            user_account: self.user_account,
+            nft_accounts: vec![self.nft_account],
+            reward_account,
+            uuid_count: 0,
+        };
+        (pool, user)
+    }
+}
+
+fn serialize<T: AccountSerialize>(account: &T) -> Vec<u8> {
+    let mut data = vec![];
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+// account data as the baseline program allocated it, zero padded up to `size`
+fn baseline_layout<T: AccountSerialize>(account: &T, size: usize) -> Vec<u8> {
+    let mut data = serialize(account);
+    assert!(data.len() <= size);
+    data.resize(size, 0);
+    data
+}
+
+fn add_account(program_test: &mut ProgramTest, address: &Pubkey, owner: &Pubkey, data: Vec<u8>) {
+    program_test.add_account(
+        *address,
+        Account {
+            lamports: USER_LAMPORTS,
+            data,
+            owner: *owner,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+}
+
+fn add_mint(program_test: &mut ProgramTest, address: &Pubkey, authority: &Pubkey, supply: u64, decimals: u8) {
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    let mint = spl_token::state::Mint {
+        mint_authority: COption::Some(*authority),
+        supply,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    };
+    mint.pack_into_slice(&mut data);
+    add_account(program_test, address, &spl_token::id(), data);
+}
+
+fn add_token_account(program_test: &mut ProgramTest, address: &Pubkey, mint: &Pubkey, owner: &Pubkey, amount: u64) {
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    let account = spl_token::state::Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..spl_token::state::Account::default()
+    };
+    account.pack_into_slice(&mut data);
+    add_account(program_test, address, &spl_token::id(), data);
+}
@@ -0,0 +1,359 @@
+// one failing case for every ErrorCode returned by the program
+// the deprecated InsufficientFundStake, InsufficientFundUnstake, SingleStakeTokenBCannotBeFunded
+// NumericalOverflowError and MaxFunders are not returned by any instruction
+
+use nft_staking_program_test::*;
+use nft_staking::constants::MAX_COUNT_BONUS;
+use nft_staking::{CountBonus, ErrorCode, FundMode};
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+const NUM_MINT: u32 = 3;
+const REWARD_DURATION: u64 = 100_000;
+const FUND_AMOUNT: u64 = 3_000_000;
+
+#[tokio::test]
+async fn initialize_pool_without_mints() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::prepare(&mut context, 0).await;
+    assert_error(pool.initialize(&mut context, 0, REWARD_DURATION).await, ErrorCode::InsufficientTokenStake);
+}
+
+#[tokio::test]
+async fn initialize_pool_with_zero_duration() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::prepare(&mut context, NUM_MINT).await;
+    assert_error(pool.initialize(&mut context, NUM_MINT, 0).await, ErrorCode::DurationTooShort);
+}
+
+#[tokio::test]
+async fn add_mint_addresses_past_num_mint() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::prepare(&mut context, NUM_MINT).await;
+    pool.initialize(&mut context, NUM_MINT, REWARD_DURATION).await.unwrap();
+    let result = pool.add_mints(&mut context, vec![pool.mints[0]], NUM_MINT).await;
+    assert_error(result, ErrorCode::IndexGreaterThanLength);
+
+    // a valid index with mints running past num_mint
+    let result = pool.add_mints(&mut context, pool.mints[..2].to_vec(), NUM_MINT - 1).await;
+    assert_error(result, ErrorCode::IndexGreaterThanLength);
+}
+
+#[tokio::test]
+async fn pause_and_resume_twice() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new(&mut context, NUM_MINT, REWARD_DURATION).await;
+
+    // the pool starts paused
+    assert_error(pool.pause(&mut context).await, ErrorCode::PoolPaused);
+    pool.resume(&mut context).await.unwrap();
+    assert_error(pool.resume(&mut context).await, ErrorCode::PoolNotPaused);
+}
+
+#[tokio::test]
+async fn funder_changes() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new(&mut context, NUM_MINT, REWARD_DURATION).await;
+    let authority = pool.authority.pubkey();
+
+    let authorize = |funder: &Pubkey| instruction::authorize_funder(&authority, &pool.pool_account, funder, u64::MAX);
+    let deauthorize = |funder: &Pubkey| instruction::deauthorize_funder(&authority, &pool.pool_account, funder);
+
+    let result = process(&mut context, &[authorize(&authority)], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::FunderAlreadyAuthorized);
+
+    let funder = Keypair::new().pubkey();
+    process(&mut context, &[authorize(&funder)], &[&pool.authority]).await.unwrap();
+    let result = process(&mut context, &[authorize(&funder)], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::FunderAlreadyAuthorized);
+
+    let result = process(&mut context, &[deauthorize(&authority)], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::CannotDeauthorizePoolAuthority);
+    let result = process(&mut context, &[deauthorize(&Keypair::new().pubkey())], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::CannotDeauthorizeMissingAuthority);
+
+    // the funder account stays, a deauthorized funder can't be deauthorized again
+    process(&mut context, &[deauthorize(&funder)], &[&pool.authority]).await.unwrap();
+    let result = process(&mut context, &[deauthorize(&funder)], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::CannotDeauthorizeMissingAuthority);
+}
+
+#[tokio::test]
+async fn stake_mint_not_in_config() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let foreign_mint = create_mint(&mut context, &pool.authority.pubkey(), 0).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &[foreign_mint, pool.mints[0]]).await;
+    let nft_accounts = user.nft_accounts.clone();
+    user.create(&mut context, &pool).await.unwrap();
+    assert_error(user.stake(&mut context, &pool, &nft_accounts[0]).await, ErrorCode::InvalidMint);
+    assert_error(user.stake_many(&mut context, &pool, &nft_accounts).await, ErrorCode::InvalidMint);
+}
+
+#[tokio::test]
+async fn stake_many_and_unstake_many_counts() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..2]).await;
+    let nft_accounts = user.nft_accounts.clone();
+    user.create(&mut context, &pool).await.unwrap();
+    assert_error(user.stake_many(&mut context, &pool, &[]).await, ErrorCode::InsufficientTokenStake);
+
+    // unstake an nft that is not staked
+    user.stake(&mut context, &pool, &nft_accounts[0]).await.unwrap();
+    assert_error(user.unstake_many(&mut context, &pool, &nft_accounts[1..]).await, ErrorCode::InvalidMint);
+
+    // the same token account twice
+    let twice = [nft_accounts[1], nft_accounts[1]];
+    assert_error(user.stake_many(&mut context, &pool, &twice).await, ErrorCode::DuplicateTokenAccount);
+    user.stake(&mut context, &pool, &nft_accounts[1]).await.unwrap();
+    assert_error(user.unstake_many(&mut context, &pool, &twice).await, ErrorCode::DuplicateTokenAccount);
+
+    // unstaking everything leaves an empty mint staked account
+    user.unstake_many(&mut context, &pool, &nft_accounts).await.unwrap();
+    let user_state = user.state(&mut context).await;
+    assert_eq!(user_state.mint_staked_count, 0);
+    assert!(get_mint_staked(&mut context, &user_state.mint_staked).await.mint_accounts.is_empty());
+}
+
+#[tokio::test]
+async fn close_user_with_staked_mint() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..1]).await;
+    let nft_account = user.nft_accounts[0];
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    assert_error(user.close(&mut context, &pool, None).await, ErrorCode::StakedMint);
+}
+
+#[tokio::test]
+async fn close_user_with_pending_rewards() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..1]).await;
+    let nft_account = user.nft_accounts[0];
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+    user.unstake(&mut context, &pool, &nft_account, None).await.unwrap();
+    assert!(user.state(&mut context).await.reward_earned_pending > 0);
+
+    assert_error(user.close(&mut context, &pool, None).await, ErrorCode::PendingRewards);
+}
+
+#[tokio::test]
+async fn claim_with_foreign_reward_accounts() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let other_pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..1]).await;
+    let nft_account = user.nft_accounts[0];
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+
+    // reward vault of another pool as the unstake reward accounts
+    let pool_state = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    let uuid = user.next_uuid();
+    let mut ix = instruction::unstake(
+        &user.pubkey(),
+        &pool.pool_account,
+        &pool_state,
+        &user_state,
+        &nft_account,
+        &uuid,
+        Some(&user.reward_account),
+    );
+    let remaining = ix.accounts.len() - 2;
+    ix.accounts[remaining].pubkey = other_pool.reward_vault;
+    let result = process(&mut context, &[ix], &[&user.keypair]).await;
+    assert_error(result, ErrorCode::InvalidRewardAccount);
+
+    // associated token account of someone else
+    let mut ix = instruction::claim_to_associated_token(&user.pubkey(), &pool.pool_account, &pool_state, &user_state);
+    let associated_token = pda::associated_token_address(&user.pubkey(), &pool.reward_mint);
+    let position = ix.accounts.iter().position(|x| x.pubkey == associated_token).unwrap();
+    ix.accounts[position].pubkey = pda::associated_token_address(&pool.authority.pubkey(), &pool.reward_mint);
+    let result = process(&mut context, &[ix], &[&user.keypair]).await;
+    assert_error(result, ErrorCode::InvalidRewardAccount);
+}
+
+#[tokio::test]
+async fn close_mint_staked_checks() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &[]).await;
+    user.create(&mut context, &pool).await.unwrap();
+    let mint_staked = user.state(&mut context).await.mint_staked;
+
+    // the current mint staked account of the user
+    let ix = instruction::close_mint_staked(&user.pubkey(), &pool.pool_account, &mint_staked);
+    let result = process(&mut context, &[ix], &[&user.keypair]).await;
+    assert_error(result, ErrorCode::MintStakedInUse);
+
+    // the mint staked account of another user
+    let other = TestUser::new(&mut context, &pool, &[]).await;
+    let mut ix = instruction::close_mint_staked(&other.pubkey(), &pool.pool_account, &mint_staked);
+    ix.accounts[2].pubkey = user.user_account;
+    let result = process(&mut context, &[ix], &[&other.keypair]).await;
+    assert_error(result, ErrorCode::InvalidUserAccount);
+}
+
+#[tokio::test]
+async fn begin_migrate_current_pool() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new(&mut context, NUM_MINT, REWARD_DURATION).await;
+
+    // a pool created with the current layout
+    let ix = instruction::begin_migrate_pool(&pool.authority.pubkey(), &pool.pool_account);
+    let result = process(&mut context, &[ix], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::AlreadyMigrated);
+}
+
+#[tokio::test]
+async fn migrate_user_with_foreign_mint_staked() {
+    let mut program_test = program_test();
+    let legacy = LegacyPool::add_to(&mut program_test, false);
+    let mut context = program_test.start_with_context().await;
+    let user = legacy.user.pubkey();
+
+    let ix = instruction::begin_migrate_pool(&legacy.authority.pubkey(), &legacy.pool_account);
+    process(&mut context, &[ix], &[&legacy.authority]).await.unwrap();
+    let ix = instruction::migrate_pool(&legacy.authority.pubkey(), &legacy.config);
+    process(&mut context, &[ix], &[&legacy.authority]).await.unwrap();
+    let ix = instruction::begin_migrate_user(&user, &legacy.pool_account);
+    process(&mut context, &[ix], &[&legacy.user]).await.unwrap();
+
+    // a program account that is not the mint staked account of the old user account
+    let ix = instruction::migrate_user(&user, &legacy.pool_account, &legacy.config, "uuid1");
+    let result = process(&mut context, &[ix], &[&legacy.user]).await;
+    assert_error(result, ErrorCode::InvalidMigration);
+}
+
+#[tokio::test]
+async fn bonus_rule_checks() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new(&mut context, NUM_MINT, REWARD_DURATION).await;
+    let authority = pool.authority.pubkey();
+
+    // more rules than the pool holds
+    let rules = vec![CountBonus { min_count: 1, bonus_bps: 100 }; MAX_COUNT_BONUS + 1];
+    let ix = instruction::set_count_bonus(&authority, &pool.pool_account, rules);
+    let result = process(&mut context, &[ix], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::InvalidBonusRule);
+
+    // the config was created without room for mint groups
+    let ix = instruction::add_mint_group(&authority, &pool.pool_account, &pool.config, 1000, pool.mints[..2].to_vec());
+    let result = process(&mut context, &[ix], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::MintGroupsFull);
+}
+
+#[tokio::test]
+async fn funder_cap_and_account() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let authority = pool.authority.pubkey();
+
+    let funder = create_funded_keypair(&mut context).await;
+    let funder_vault = create_token_account(&mut context, &pool.reward_mint, &funder.pubkey()).await;
+    mint_to(&mut context, &pool.reward_mint, &funder_vault, &pool.authority, FUND_AMOUNT).await;
+    let ix = instruction::authorize_funder(&authority, &pool.pool_account, &funder.pubkey(), FUND_AMOUNT / 2);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+
+    let pool_state = pool.state(&mut context).await;
+    let fund = |amount: u64| instruction::fund(&funder.pubkey(), &pool.pool_account, &pool_state, &funder_vault, amount, FundMode::Reset);
+    process(&mut context, &[fund(FUND_AMOUNT / 4)], &[&funder]).await.unwrap();
+    let result = process(&mut context, &[fund(FUND_AMOUNT / 2)], &[&funder]).await;
+    assert_error(result, ErrorCode::FunderCapExceeded);
+
+    // the funder account of another funder
+    let other = Keypair::new().pubkey();
+    let ix = instruction::authorize_funder(&authority, &pool.pool_account, &other, FUND_AMOUNT);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    let mut ix = fund(FUND_AMOUNT / 4);
+    ix.accounts[6].pubkey = pda::funder_address(&pool.pool_account, &other).0;
+    let result = process(&mut context, &[ix], &[&funder]).await;
+    assert_error(result, ErrorCode::InvalidFunderAccount);
+
+    // a deauthorized funder keeps its funder account but can't fund
+    let ix = instruction::deauthorize_funder(&authority, &pool.pool_account, &funder.pubkey());
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    let result = process(&mut context, &[fund(1)], &[&funder]).await;
+    assert_error(result, ErrorCode::InvalidFunderAccount);
+}
+
+#[tokio::test]
+async fn top_up_without_rate() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new(&mut context, NUM_MINT, REWARD_DURATION).await;
+    pool.resume(&mut context).await.unwrap();
+
+    let pool_state = pool.state(&mut context).await;
+    let ix = instruction::fund(&pool.authority.pubkey(), &pool.pool_account, &pool_state, &pool.funder_vault, FUND_AMOUNT, FundMode::TopUp);
+    let result = process(&mut context, &[ix], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::TopUpWithoutRate);
+    let ix = instruction::donate(&pool.authority.pubkey(), &pool.pool_account, &pool_state, &pool.funder_vault, FUND_AMOUNT);
+    let result = process(&mut context, &[ix], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::TopUpWithoutRate);
+
+    // the rate of a reward period that is over is not topped up either
+    pool.fund(&mut context, FUND_AMOUNT).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION + 1).await;
+    let pool_state = pool.state(&mut context).await;
+    let ix = instruction::fund(&pool.authority.pubkey(), &pool.pool_account, &pool_state, &pool.funder_vault, FUND_AMOUNT, FundMode::TopUp);
+    let result = process(&mut context, &[ix], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::TopUpWithoutRate);
+    let ix = instruction::donate(&pool.authority.pubkey(), &pool.pool_account, &pool_state, &pool.funder_vault, FUND_AMOUNT);
+    let result = process(&mut context, &[ix], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::TopUpWithoutRate);
+}
+
+#[tokio::test]
+async fn donate_past_the_end_of_time() {
+    let mut context = program_test().start_with_context().await;
+    // the lowest rate, a token unit for the whole period
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, 1).await;
+    let amount = u64::MAX / REWARD_DURATION * 2;
+    mint_to(&mut context, &pool.reward_mint, &pool.funder_vault, &pool.authority, amount).await;
+
+    let pool_state = pool.state(&mut context).await;
+    let ix = instruction::donate(&pool.authority.pubkey(), &pool.pool_account, &pool_state, &pool.funder_vault, amount);
+    let result = process(&mut context, &[ix], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::NumericalOverflowError);
+    let ix = instruction::fund(&pool.authority.pubkey(), &pool.pool_account, &pool_state, &pool.funder_vault, amount, FundMode::TopUp);
+    let result = process(&mut context, &[ix], &[&pool.authority]).await;
+    assert_error(result, ErrorCode::NumericalOverflowError);
+}
+
+#[tokio::test]
+async fn fund_validation() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let authority = pool.authority.pubkey();
+    let pool_state = pool.state(&mut context).await;
+
+    let ix = instruction::fund(&authority, &pool.pool_account, &pool_state, &pool.funder_vault, 0, FundMode::Reset);
+    assert_error(process(&mut context, &[ix], &[&pool.authority]).await, ErrorCode::AmountMustBeGreaterThanZero);
+    let ix = instruction::donate(&authority, &pool.pool_account, &pool_state, &pool.funder_vault, 0);
+    assert_error(process(&mut context, &[ix], &[&pool.authority]).await, ErrorCode::AmountMustBeGreaterThanZero);
+    let ix = instruction::preview_fund(&pool.pool_account, &pool_state, 0, FundMode::Reset);
+    assert_error(process(&mut context, &[ix], &[]).await, ErrorCode::AmountMustBeGreaterThanZero);
+
+    // a vault of another mint
+    let other_mint = create_mint(&mut context, &authority, 6).await;
+    let other_vault = create_token_account(&mut context, &other_mint, &authority).await;
+    mint_to(&mut context, &other_mint, &other_vault, &pool.authority, FUND_AMOUNT).await;
+    let ix = instruction::fund(&authority, &pool.pool_account, &pool_state, &other_vault, FUND_AMOUNT, FundMode::Reset);
+    assert_constraint_error(process(&mut context, &[ix], &[&pool.authority]).await);
+    let ix = instruction::donate(&authority, &pool.pool_account, &pool_state, &other_vault, FUND_AMOUNT);
+    assert_constraint_error(process(&mut context, &[ix], &[&pool.authority]).await);
+}
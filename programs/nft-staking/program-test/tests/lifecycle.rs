@@ -0,0 +1,668 @@
+use nft_staking_program_test::*;
+use nft_staking::utils::{fund_schedule, pending_rewards};
+use nft_staking::{CountBonus, ErrorCode, FundMode, User};
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use solana_sdk::system_instruction;
+
+const NUM_MINT: u32 = 3;
+const REWARD_DURATION: u64 = 100_000;
+const FUND_AMOUNT: u64 = 3_000_000;
+
+#[tokio::test]
+async fn full_lifecycle() {
+    let mut context = program_test().start_with_context().await;
+
+    // init, add mints
+    let pool = TestPool::new(&mut context, NUM_MINT, REWARD_DURATION).await;
+    let pool_state = pool.state(&mut context).await;
+    assert!(pool_state.is_initialized);
+    assert!(pool_state.paused);
+    assert_eq!(pool_state.reward_vault, pool.reward_vault);
+
+    // resume, fund
+    pool.resume(&mut context).await.unwrap();
+    pool.fund(&mut context, FUND_AMOUNT).await.unwrap();
+    assert_eq!(token_balance(&mut context, &pool.reward_vault).await, FUND_AMOUNT);
+    assert_eq!(token_balance(&mut context, &pool.funder_vault).await, REWARD_SUPPLY - FUND_AMOUNT);
+    let pool_state = pool.state(&mut context).await;
+    assert!(pool_state.reward_duration_end > 0);
+    assert_eq!(pool_state.total_funded, FUND_AMOUNT);
+    assert_eq!(pool_state.last_fund_time, now(&mut context).await);
+
+    // create user, stake
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..1]).await;
+    let nft_account = user.nft_accounts[0];
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_account).await.unwrap();
+
+    let pool_state = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    assert_eq!(pool_state.user_count, 1);
+    assert_eq!(pool_state.token_stake_count, 1);
+    assert_eq!(user_state.mint_staked_count, 1);
+    let mint_staked = get_mint_staked(&mut context, &user_state.mint_staked).await;
+    assert_eq!(mint_staked.mint_accounts, vec![nft_account]);
+    assert_eq!(get_token_account(&mut context, &nft_account).await.owner, pool.pool_account);
+
+    // warp into the reward period
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+
+    // claim
+    let pool_state = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    let stake_history = get_mint_staked(&mut context, &user_state.mint_staked).await.stake_history;
+    user.claim(&mut context, &pool).await.unwrap();
+    let (expected, _) = pending_rewards(&pool_state, &user_state, &stake_history, now(&mut context).await);
+    assert!(expected > 0);
+    // one of the three mints is staked, at most a third of the funding
+    assert!(expected <= FUND_AMOUNT / NUM_MINT as u64);
+    assert_eq!(token_balance(&mut context, &user.reward_account).await, expected);
+    let user_state = user.state(&mut context).await;
+    assert_eq!(user_state.reward_earned_claimed, expected);
+    assert_eq!(user_state.reward_earned_pending, 0);
+
+    // unstake and claim the rest
+    let reward_account = user.reward_account;
+    user.unstake(&mut context, &pool, &nft_account, Some(&reward_account)).await.unwrap();
+    assert_eq!(get_token_account(&mut context, &nft_account).await.owner, user.pubkey());
+    assert_eq!(pool.state(&mut context).await.token_stake_count, 0);
+    let user_state = user.state(&mut context).await;
+    assert_eq!(user_state.mint_staked_count, 0);
+    assert_eq!(user_state.reward_earned_pending, 0);
+    assert_eq!(user_state.reward_owed, 0);
+    assert!(get_mint_staked(&mut context, &user_state.mint_staked).await.mint_accounts.is_empty());
+    let claimed = token_balance(&mut context, &user.reward_account).await;
+    assert_eq!(user_state.reward_earned_claimed, claimed);
+    let pool_state = pool.state(&mut context).await;
+    assert_eq!(pool_state.total_claimed, claimed);
+    assert_eq!(pool_state.total_owed_estimate, 0);
+    assert_eq!(pool_state.peak_staked, 1);
+
+    // close user
+    user.close(&mut context, &pool, None).await.unwrap();
+    assert!(!account_exists(&mut context, &user.user_account).await);
+    assert!(!account_exists(&mut context, &user_state.mint_staked).await);
+    assert_eq!(pool.state(&mut context).await.user_count, 0);
+
+    // close pool after the reward period, the rest of the funding goes back to the authority
+    warp_seconds(&mut context, REWARD_DURATION).await;
+    let pool_state = pool.state(&mut context).await;
+    let ix = instruction::close_pool(
+        &pool.authority.pubkey(),
+        &pool.pool_account,
+        &pool_state,
+        &pool.authority.pubkey(),
+        &pool.funder_vault,
+        &[],
+    );
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    assert!(!account_exists(&mut context, &pool.pool_account).await);
+    assert!(!account_exists(&mut context, &pool.reward_vault).await);
+    assert_eq!(token_balance(&mut context, &pool.funder_vault).await, REWARD_SUPPLY - claimed);
+}
+
+#[tokio::test]
+async fn unstake_and_close_user_claim_pending_rewards() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..2]).await;
+    let nft_accounts = user.nft_accounts.clone();
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake_many(&mut context, &pool, &nft_accounts).await.unwrap();
+    assert_eq!(user.state(&mut context).await.mint_staked_count, 2);
+    assert_eq!(pool.state(&mut context).await.token_stake_count, 2);
+
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+
+    // unstake one nft and claim in the same instruction
+    let reward_account = user.reward_account;
+    user.unstake(&mut context, &pool, &nft_accounts[0], Some(&reward_account)).await.unwrap();
+    let claimed = token_balance(&mut context, &user.reward_account).await;
+    assert!(claimed > 0);
+    let user_state = user.state(&mut context).await;
+    assert_eq!(user_state.reward_earned_claimed, claimed);
+    assert_eq!(user_state.reward_earned_pending, 0);
+    assert_eq!(user_state.mint_staked_count, 1);
+
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+
+    user.unstake_many(&mut context, &pool, &nft_accounts[1..]).await.unwrap();
+    assert_eq!(get_token_account(&mut context, &nft_accounts[1]).await.owner, user.pubkey());
+    let user_state = user.state(&mut context).await;
+    assert!(user_state.reward_earned_pending > 0);
+
+    // closing the user pays out the rest
+    user.close(&mut context, &pool, Some(&reward_account)).await.unwrap();
+    assert!(token_balance(&mut context, &user.reward_account).await >= claimed + user_state.reward_earned_pending);
+    assert!(!account_exists(&mut context, &user.user_account).await);
+}
+
+#[tokio::test]
+async fn emergency_unstake_while_paused() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..1]).await;
+    let nft_account = user.nft_accounts[0];
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    pool.pause(&mut context).await.unwrap();
+
+    // the regular unstake is closed while paused
+    assert_error(user.unstake(&mut context, &pool, &nft_account, None).await, ErrorCode::PoolPaused);
+
+    let pool_state = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    let uuid = user.next_uuid();
+    let ix = instruction::emergency_unstake(&user.pubkey(), &pool.pool_account, &pool_state, &user_state, &nft_account, &uuid);
+    process(&mut context, &[ix], &[&user.keypair]).await.unwrap();
+    assert_eq!(get_token_account(&mut context, &nft_account).await.owner, user.pubkey());
+    assert_eq!(pool.state(&mut context).await.token_stake_count, 0);
+    assert_eq!(user.state(&mut context).await.mint_staked_count, 0);
+}
+
+#[tokio::test]
+async fn claim_for_requires_the_delegate() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..1]).await;
+    let nft_account = user.nft_accounts[0];
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+
+    // nobody can claim for a user without a delegate
+    let crank = create_funded_keypair(&mut context).await;
+    let pool_state = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    let ix = instruction::claim_for(&crank.pubkey(), &user.pubkey(), &pool.pool_account, &pool_state, &user_state, &user.reward_account);
+    assert_constraint_error(process(&mut context, &[ix], &[&crank]).await);
+
+    let delegate = create_funded_keypair(&mut context).await;
+    let ix = instruction::set_claim_delegate(&user.pubkey(), &pool.pool_account, &delegate.pubkey(), &Pubkey::default());
+    process(&mut context, &[ix], &[&user.keypair]).await.unwrap();
+    let ix = instruction::claim_for(&crank.pubkey(), &user.pubkey(), &pool.pool_account, &pool_state, &user_state, &user.reward_account);
+    assert_constraint_error(process(&mut context, &[ix], &[&crank]).await);
+
+    let ix = instruction::claim_for(&delegate.pubkey(), &user.pubkey(), &pool.pool_account, &pool_state, &user_state, &user.reward_account);
+    process(&mut context, &[ix], &[&delegate]).await.unwrap();
+    assert!(token_balance(&mut context, &user.reward_account).await > 0);
+    assert_eq!(user.state(&mut context).await.reward_earned_pending, 0);
+}
+
+#[tokio::test]
+async fn claim_to_associated_token_creates_the_account() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..1]).await;
+    let nft_account = user.nft_accounts[0];
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+
+    let associated_token = pda::associated_token_address(&user.pubkey(), &pool.reward_mint);
+    assert!(!account_exists(&mut context, &associated_token).await);
+    let pool_state = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    let ix = instruction::claim_to_associated_token(&user.pubkey(), &pool.pool_account, &pool_state, &user_state);
+    process(&mut context, &[ix], &[&user.keypair]).await.unwrap();
+
+    let claimed = token_balance(&mut context, &associated_token).await;
+    assert!(claimed > 0);
+    assert_eq!(user.state(&mut context).await.reward_earned_claimed, claimed);
+}
+
+#[tokio::test]
+async fn migrate_legacy_pool_and_user() {
+    let mut program_test = program_test();
+    let legacy = LegacyPool::add_to(&mut program_test, false);
+    let mut context = program_test.start_with_context().await;
+    let authority = legacy.authority.pubkey();
+    let user = legacy.user.pubkey();
+
+    // pool first, the reward vault stays where it is
+    let ix = instruction::begin_migrate_pool(&authority, &legacy.pool_account);
+    process(&mut context, &[ix], &[&legacy.authority]).await.unwrap();
+    assert!(!account_exists(&mut context, &legacy.pool_account).await);
+    let ix = instruction::migrate_pool(&authority, &legacy.config);
+    process(&mut context, &[ix], &[&legacy.authority]).await.unwrap();
+
+    let pool_state = get_pool(&mut context, &legacy.pool_account).await;
+    assert_eq!(pool_state.version, nft_staking::constants::ACCOUNT_VERSION);
+    assert_eq!(pool_state.is_initialized, legacy.pool.is_initialized);
+    assert_eq!(pool_state.authority, legacy.pool.authority);
+    assert_eq!(pool_state.paused, legacy.pool.paused);
+    assert_eq!(pool_state.config, legacy.pool.config);
+    assert_eq!(pool_state.reward_mint, legacy.pool.reward_mint);
+    assert_eq!(pool_state.reward_vault, legacy.pool.reward_vault);
+    assert_eq!(pool_state.last_update_time, legacy.pool.last_update_time);
+    assert_eq!(pool_state.reward_rate_per_token, legacy.pool.reward_rate_per_token);
+    assert_eq!(pool_state.reward_duration, legacy.pool.reward_duration);
+    assert_eq!(pool_state.reward_duration_end, legacy.pool.reward_duration_end);
+    assert_eq!(pool_state.token_stake_count, legacy.pool.token_stake_count);
+    assert_eq!(pool_state.user_count, legacy.pool.user_count);
+    assert_eq!(pool_state.legacy_funders, legacy.pool.funders);
+    assert_eq!(pool_state.paused_at, 0);
+    assert_eq!(pool_state.paused_duration, 0);
+    assert_eq!(token_balance(&mut context, &legacy.reward_vault).await, LegacyPool::REWARD_VAULT_AMOUNT);
+    assert!(!account_exists(&mut context, &pda::migration_address(&legacy.pool_account).0).await);
+
+    // then the user, its mint staked account moves to a new uuid
+    let ix = instruction::begin_migrate_user(&user, &legacy.pool_account);
+    process(&mut context, &[ix], &[&legacy.user]).await.unwrap();
+    let ix = instruction::migrate_user(&user, &legacy.pool_account, &legacy.mint_staked, "uuid1");
+    process(&mut context, &[ix], &[&legacy.user]).await.unwrap();
+    let migrated_at = now(&mut context).await;
+
+    let user_state = get_user(&mut context, &legacy.user_account).await;
+    assert_eq!(user_state.version, nft_staking::constants::ACCOUNT_VERSION);
+    assert_eq!(user_state.pool, legacy.user_state.pool);
+    assert_eq!(user_state.user, legacy.user_state.user);
+    assert_eq!(user_state.reward_earned_claimed, legacy.user_state.reward_earned_claimed);
+    assert_eq!(user_state.mint_staked_count, legacy.user_state.mint_staked_count);
+    assert_eq!(user_state.uuid, "uuid1");
+    // the stake time since the legacy update is settled at the legacy rate, one token per nft second
+    let unsettled = migrated_at - legacy.user_state.last_update_time;
+    assert_eq!(user_state.reward_earned_pending, legacy.user_state.reward_earned_pending + unsettled);
+    assert_eq!(user_state.last_update_time, migrated_at);
+    assert_eq!(user_state.reward_owed, 0);
+    let mint_staked = get_mint_staked(&mut context, &user_state.mint_staked).await;
+    assert_eq!(mint_staked.mint_accounts, vec![legacy.nft_account]);
+    assert_eq!(mint_staked.version, nft_staking::constants::ACCOUNT_VERSION);
+    assert!(!account_exists(&mut context, &legacy.mint_staked).await);
+    assert!(!account_exists(&mut context, &pda::migration_address(&legacy.user_account).0).await);
+
+    // the migrated accounts work with the current instructions
+    let ix = instruction::unstake(&user, &legacy.pool_account, &pool_state, &user_state, &legacy.nft_account, "uuid2", None);
+    process(&mut context, &[ix], &[&legacy.user]).await.unwrap();
+    assert_eq!(get_token_account(&mut context, &legacy.nft_account).await.owner, user);
+    assert_eq!(get_pool(&mut context, &legacy.pool_account).await.token_stake_count, 0);
+}
+
+#[tokio::test]
+async fn migrate_paused_legacy_pool_and_resume() {
+    let mut program_test = program_test();
+    let legacy = LegacyPool::add_to(&mut program_test, true);
+    let mut context = program_test.start_with_context().await;
+    let authority = legacy.authority.pubkey();
+
+    let ix = instruction::begin_migrate_pool(&authority, &legacy.pool_account);
+    process(&mut context, &[ix], &[&legacy.authority]).await.unwrap();
+    let ix = instruction::migrate_pool(&authority, &legacy.config);
+    process(&mut context, &[ix], &[&legacy.authority]).await.unwrap();
+    let migrated_at = now(&mut context).await;
+
+    // the pause counts from the migration, not from the epoch
+    let pool_state = get_pool(&mut context, &legacy.pool_account).await;
+    assert!(pool_state.paused);
+    assert_eq!(pool_state.paused_at, migrated_at);
+    assert_eq!(pool_state.paused_duration, 0);
+
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+    let ix = instruction::resume(&authority, &legacy.pool_account);
+    process(&mut context, &[ix], &[&legacy.authority]).await.unwrap();
+    let paused_interval = now(&mut context).await - migrated_at;
+
+    let pool_state = get_pool(&mut context, &legacy.pool_account).await;
+    assert!(!pool_state.paused);
+    assert_eq!(pool_state.paused_duration, paused_interval);
+    assert_eq!(pool_state.reward_duration_end, legacy.pool.reward_duration_end + paused_interval);
+}
+
+#[tokio::test]
+async fn stake_history_survives_restake() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..2]).await;
+    let nft_accounts = user.nft_accounts.clone();
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_accounts[0]).await.unwrap();
+    let staked_at = now(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    assert_eq!(user_state.stake_history_count, 1);
+    let history = get_mint_staked(&mut context, &user_state.mint_staked).await.stake_history;
+    assert_eq!(history[0].mint, pool.mints[0]);
+    assert_eq!(history[0].staked_at, staked_at);
+
+    // unstaking stops the clock, the entry stays
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+    user.unstake(&mut context, &pool, &nft_accounts[0], None).await.unwrap();
+    let first_stake = now(&mut context).await - staked_at;
+    let user_state = user.state(&mut context).await;
+    let history = get_mint_staked(&mut context, &user_state.mint_staked).await.stake_history;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].staked_at, 0);
+    assert_eq!(history[0].staked_seconds, first_stake);
+
+    // restaking the same nft with another one keeps the staked seconds and adds one entry
+    user.stake_many(&mut context, &pool, &nft_accounts).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+    user.unstake_many(&mut context, &pool, &nft_accounts).await.unwrap();
+    let user_state = user.state(&mut context).await;
+    assert_eq!(user_state.stake_history_count, 2);
+    let history = get_mint_staked(&mut context, &user_state.mint_staked).await.stake_history;
+    assert!(history.iter().all(|x| x.staked_at == 0));
+    assert_eq!(history[0].staked_seconds, first_stake + history[1].staked_seconds);
+}
+
+#[tokio::test]
+async fn loyalty_bonus_grows_with_stake_time() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    // +10% per 1000 seconds up to +30%
+    let ix = instruction::set_loyalty_ramp(&pool.authority.pubkey(), &pool.pool_account, 1000, 1000, 3000);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..1]).await;
+    let nft_account = user.nft_accounts[0];
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+
+    let pool_state = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    let stake_history = get_mint_staked(&mut context, &user_state.mint_staked).await.stake_history;
+    user.claim(&mut context, &pool).await.unwrap();
+    let now = now(&mut context).await;
+    let (expected, _) = pending_rewards(&pool_state, &user_state, &stake_history, now);
+    let (base, _) = pending_rewards(&pool_state, &user_state, &[], now);
+    assert_eq!(token_balance(&mut context, &user.reward_account).await, expected);
+    assert!(expected > base);
+    assert!(expected <= base + base * 3 / 10);
+}
+
+#[tokio::test]
+async fn set_bonus_for_count_and_mint_group() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::prepare(&mut context, NUM_MINT).await;
+    // room for one group of two mints
+    pool.initialize_with_mint_groups(&mut context, NUM_MINT, REWARD_DURATION, 4 + 8 + 32 * 2).await.unwrap();
+    pool.add_mints(&mut context, pool.mints.clone(), 0).await.unwrap();
+    pool.resume(&mut context).await.unwrap();
+    pool.fund(&mut context, FUND_AMOUNT).await.unwrap();
+
+    let authority = pool.authority.pubkey();
+    let rules = vec![CountBonus { min_count: 3, bonus_bps: 2000 }];
+    let ix = instruction::set_count_bonus(&authority, &pool.pool_account, rules);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    let ix = instruction::add_mint_group(&authority, &pool.pool_account, &pool.config, 1000, pool.mints[..2].to_vec());
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints).await;
+    let nft_accounts = user.nft_accounts.clone();
+    user.create(&mut context, &pool).await.unwrap();
+
+    // the group is complete with the second nft, the count rule with the third
+    let mut set_bonus = vec![];
+    for nft_account in &nft_accounts {
+        user.stake(&mut context, &pool, nft_account).await.unwrap();
+        set_bonus.push(user.state(&mut context).await.set_bonus_bps);
+    }
+    assert_eq!(set_bonus, vec![0, 1000, 3000]);
+
+    warp_seconds(&mut context, REWARD_DURATION / 10).await;
+    let pool_state = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    let stake_history = get_mint_staked(&mut context, &user_state.mint_staked).await.stake_history;
+    user.claim(&mut context, &pool).await.unwrap();
+    let now = now(&mut context).await;
+    let (expected, _) = pending_rewards(&pool_state, &user_state, &stake_history, now);
+    let base = User { set_bonus_bps: 0, ..user_state.clone() };
+    let (base, _) = pending_rewards(&pool_state, &base, &stake_history, now);
+    assert_eq!(token_balance(&mut context, &user.reward_account).await, expected);
+    // everything accrued since the third stake carries 30%
+    let settled = user_state.reward_earned_pending;
+    assert_eq!(expected - base, (base - settled) * 3 / 10);
+
+    // unstaking a group mint drops both bonuses
+    user.unstake(&mut context, &pool, &nft_accounts[0], None).await.unwrap();
+    assert_eq!(user.state(&mut context).await.set_bonus_bps, 0);
+}
+
+#[tokio::test]
+async fn funder_refunded_on_close_pool() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let authority = pool.authority.pubkey();
+
+    // a funder capped at its funding
+    let funder = create_funded_keypair(&mut context).await;
+    let funder_vault = create_token_account(&mut context, &pool.reward_mint, &funder.pubkey()).await;
+    mint_to(&mut context, &pool.reward_mint, &funder_vault, &pool.authority, FUND_AMOUNT).await;
+    let ix = instruction::authorize_funder(&authority, &pool.pool_account, &funder.pubkey(), FUND_AMOUNT);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    let pool_state = pool.state(&mut context).await;
+    let ix = instruction::fund(&funder.pubkey(), &pool.pool_account, &pool_state, &funder_vault, FUND_AMOUNT, FundMode::Reset);
+    process(&mut context, &[ix], &[&funder]).await.unwrap();
+    let (funder_account, _) = pda::funder_address(&pool.pool_account, &funder.pubkey());
+    let funder_state = get_funder(&mut context, &funder_account).await;
+    assert_eq!(funder_state.funded, FUND_AMOUNT);
+    assert_eq!(funder_state.cap, FUND_AMOUNT);
+
+    // one nft staked for the whole period
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints[..1]).await;
+    let nft_account = user.nft_accounts[0];
+    user.create(&mut context, &pool).await.unwrap();
+    user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION + 1).await;
+    let reward_account = user.reward_account;
+    user.unstake(&mut context, &pool, &nft_account, Some(&reward_account)).await.unwrap();
+    user.close(&mut context, &pool, None).await.unwrap();
+    let claimed = token_balance(&mut context, &user.reward_account).await;
+    assert!(claimed > 0);
+
+    // a deauthorized funder is still refunded
+    let ix = instruction::deauthorize_funder(&authority, &pool.pool_account, &funder.pubkey());
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+
+    // the funder funded half of the pool and gets half of what is left
+    let pool_state = pool.state(&mut context).await;
+    assert_eq!(pool_state.total_funder_funded, FUND_AMOUNT);
+    let left = 2 * FUND_AMOUNT - claimed;
+    let ix = instruction::close_pool(&authority, &pool.pool_account, &pool_state, &authority, &pool.funder_vault, &[(funder.pubkey(), funder_vault)]);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &funder_vault).await, left / 2);
+    assert_eq!(token_balance(&mut context, &pool.funder_vault).await, REWARD_SUPPLY - FUND_AMOUNT + left - left / 2);
+    assert!(!account_exists(&mut context, &funder_account).await);
+}
+
+#[tokio::test]
+async fn funders_are_not_limited() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let authority = pool.authority.pubkey();
+
+    // more funders than the old list held, each funds once
+    let mut funders = vec![];
+    for _ in 0..6 {
+        let funder = create_funded_keypair(&mut context).await;
+        let funder_vault = create_token_account(&mut context, &pool.reward_mint, &funder.pubkey()).await;
+        mint_to(&mut context, &pool.reward_mint, &funder_vault, &pool.authority, FUND_AMOUNT).await;
+        let ix = instruction::authorize_funder(&authority, &pool.pool_account, &funder.pubkey(), FUND_AMOUNT);
+        process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+        let pool_state = pool.state(&mut context).await;
+        let ix = instruction::fund(&funder.pubkey(), &pool.pool_account, &pool_state, &funder_vault, FUND_AMOUNT, FundMode::Reset);
+        process(&mut context, &[ix], &[&funder]).await.unwrap();
+        funders.push(funder);
+    }
+    let pool_state = pool.state(&mut context).await;
+    assert_eq!(pool_state.funder_count, 6);
+    assert_eq!(pool_state.total_funded, 7 * FUND_AMOUNT);
+
+    // deauthorizing keeps the funder account and what it funded
+    let funder = funders[0].pubkey();
+    let funder_address = pda::funder_address(&pool.pool_account, &funder).0;
+    let ix = instruction::deauthorize_funder(&authority, &pool.pool_account, &funder);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    let funder_state = get_funder(&mut context, &funder_address).await;
+    assert!(!funder_state.authorized);
+    assert_eq!(funder_state.funded, FUND_AMOUNT);
+    assert_eq!(pool.state(&mut context).await.funder_count, 5);
+
+    // and the funder can be authorized again on the same account
+    let ix = instruction::authorize_funder(&authority, &pool.pool_account, &funder, 2 * FUND_AMOUNT);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    let funder_state = get_funder(&mut context, &funder_address).await;
+    assert!(funder_state.authorized);
+    assert_eq!(funder_state.funded, FUND_AMOUNT);
+    assert_eq!(funder_state.cap, 2 * FUND_AMOUNT);
+    assert_eq!(pool.state(&mut context).await.funder_count, 6);
+}
+
+#[tokio::test]
+async fn authorize_funder_with_lamports_at_the_address() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new(&mut context, NUM_MINT, REWARD_DURATION).await;
+    let authority = pool.authority.pubkey();
+
+    // anyone can send lamports to the funder address before it is created
+    let funder = Pubkey::new_unique();
+    let funder_address = pda::funder_address(&pool.pool_account, &funder).0;
+    let ix = system_instruction::transfer(&context.payer.pubkey(), &funder_address, 1_000);
+    process(&mut context, &[ix], &[]).await.unwrap();
+
+    let ix = instruction::authorize_funder(&authority, &pool.pool_account, &funder, FUND_AMOUNT);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    let funder_account = get_funder(&mut context, &funder_address).await;
+    assert_eq!(funder_account.funder, funder);
+    assert_eq!(funder_account.cap, FUND_AMOUNT);
+    assert_eq!(pool.state(&mut context).await.funder_count, 1);
+}
+
+#[tokio::test]
+async fn top_up_extends_the_reward_period() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let funded = pool.state(&mut context).await;
+
+    // half the funding again halfway through keeps the rate and adds half a period
+    warp_seconds(&mut context, REWARD_DURATION / 2).await;
+    let ix = instruction::fund(&pool.authority.pubkey(), &pool.pool_account, &funded, &pool.funder_vault, FUND_AMOUNT / 2, FundMode::TopUp);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    let pool_state = pool.state(&mut context).await;
+    assert_eq!(pool_state.reward_rate_per_token, funded.reward_rate_per_token);
+    assert_eq!(pool_state.reward_duration_end, funded.reward_duration_end + REWARD_DURATION / 2);
+    // the time before the top-up accrued at the kept rate
+    let elapsed = now(&mut context).await - funded.last_update_time;
+    assert_eq!(pool_state.reward_per_token_stored, funded.reward_rate_per_token * elapsed as u128);
+    assert_eq!(pool_state.total_funded, FUND_AMOUNT + FUND_AMOUNT / 2);
+
+    // a reset at the same point spreads everything left over a new period
+    let ix = instruction::fund(&pool.authority.pubkey(), &pool.pool_account, &pool_state, &pool.funder_vault, FUND_AMOUNT / 2, FundMode::Reset);
+    process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+    let pool_state = pool.state(&mut context).await;
+    assert_eq!(pool_state.reward_duration_end, now(&mut context).await + REWARD_DURATION);
+    assert!(pool_state.reward_rate_per_token > funded.reward_rate_per_token);
+}
+
+#[tokio::test]
+async fn donation_keeps_the_rate() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let funded = pool.state(&mut context).await;
+
+    // anyone holding reward tokens, not a funder
+    let donor = create_funded_keypair(&mut context).await;
+    let donor_vault = create_token_account(&mut context, &pool.reward_mint, &donor.pubkey()).await;
+    mint_to(&mut context, &pool.reward_mint, &donor_vault, &pool.authority, FUND_AMOUNT).await;
+    let ix = instruction::donate(&donor.pubkey(), &pool.pool_account, &funded, &donor_vault, FUND_AMOUNT / 10);
+    process(&mut context, &[ix], &[&donor]).await.unwrap();
+
+    let pool_state = pool.state(&mut context).await;
+    assert_eq!(pool_state.reward_rate_per_token, funded.reward_rate_per_token);
+    assert_eq!(pool_state.reward_duration_end, funded.reward_duration_end + REWARD_DURATION / 10);
+    assert_eq!(pool_state.total_donated, FUND_AMOUNT / 10);
+    assert_eq!(pool_state.total_funded, FUND_AMOUNT);
+    assert_eq!(token_balance(&mut context, &pool.reward_vault).await, FUND_AMOUNT + FUND_AMOUNT / 10);
+}
+
+#[tokio::test]
+async fn rewards_stop_at_the_reward_duration_end() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints).await;
+    user.create(&mut context, &pool).await.unwrap();
+    for nft_account in user.nft_accounts.clone() {
+        user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    }
+
+    // every nft staked until the end and well past it earns the rate up to the end only
+    warp_seconds(&mut context, 2 * REWARD_DURATION).await;
+    user.claim(&mut context, &pool).await.unwrap();
+    let pool_state = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    let stake_history = get_mint_staked(&mut context, &user_state.mint_staked).await.stake_history;
+    let tokens_per_second = (pool_state.reward_rate_per_token / u64::MAX as u128) as u64;
+    let expected: u64 = stake_history
+        .iter()
+        .map(|x| tokens_per_second * (pool_state.reward_duration_end - x.staked_at))
+        .sum();
+    assert_eq!(token_balance(&mut context, &user.reward_account).await, expected);
+    assert!(expected <= FUND_AMOUNT);
+}
+
+#[tokio::test]
+async fn gap_between_periods_earns_nothing() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    let mut user = TestUser::new(&mut context, &pool, &pool.mints).await;
+    user.create(&mut context, &pool).await.unwrap();
+    for nft_account in user.nft_accounts.clone() {
+        user.stake(&mut context, &pool, &nft_account).await.unwrap();
+    }
+    let first = pool.state(&mut context).await;
+    let user_state = user.state(&mut context).await;
+    let stake_history = get_mint_staked(&mut context, &user_state.mint_staked).await.stake_history;
+
+    // the first period ends, the pool is paused and resumed in the gap, then a reset starts the second period
+    // the stake time is unsettled across the reset
+    warp_seconds(&mut context, REWARD_DURATION + REWARD_DURATION / 4).await;
+    pool.pause(&mut context).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION / 4).await;
+    pool.resume(&mut context).await.unwrap();
+    warp_seconds(&mut context, REWARD_DURATION / 4).await;
+    pool.fund(&mut context, FUND_AMOUNT).await.unwrap();
+    let second = pool.state(&mut context).await;
+    warp_seconds(&mut context, 2 * REWARD_DURATION).await;
+    user.claim(&mut context, &pool).await.unwrap();
+
+    // each period pays its own rate up to its own end, the gap pays nothing
+    let tokens_per_second = |rate: u128| (rate / u64::MAX as u128) as u64;
+    let first_period: u64 = stake_history
+        .iter()
+        .map(|x| tokens_per_second(first.reward_rate_per_token) * (first.reward_duration_end - x.staked_at))
+        .sum();
+    let second_period = tokens_per_second(second.reward_rate_per_token) * u64::from(NUM_MINT) * REWARD_DURATION;
+    assert_eq!(second.reward_duration_end, second.last_fund_time + REWARD_DURATION);
+    assert_eq!(token_balance(&mut context, &user.reward_account).await, first_period + second_period);
+    assert!(first_period + second_period <= 2 * FUND_AMOUNT);
+}
+
+#[tokio::test]
+async fn preview_fund_matches_fund() {
+    let mut context = program_test().start_with_context().await;
+    let pool = TestPool::new_funded(&mut context, NUM_MINT, REWARD_DURATION, FUND_AMOUNT).await;
+    warp_seconds(&mut context, REWARD_DURATION / 4).await;
+
+    // the preview leaves the pool and the vault as they are
+    let pool_state = pool.state(&mut context).await;
+    let ix = instruction::preview_fund(&pool.pool_account, &pool_state, FUND_AMOUNT, FundMode::Reset);
+    process(&mut context, &[ix], &[]).await.unwrap();
+    assert_eq!(pool.state(&mut context).await.reward_duration_end, pool_state.reward_duration_end);
+    assert_eq!(token_balance(&mut context, &pool.reward_vault).await, FUND_AMOUNT);
+
+    for mode in [FundMode::TopUp, FundMode::Reset] {
+        let before = pool.state(&mut context).await;
+        let ix = instruction::fund(&pool.authority.pubkey(), &pool.pool_account, &before, &pool.funder_vault, FUND_AMOUNT, mode);
+        process(&mut context, &[ix], &[&pool.authority]).await.unwrap();
+        let expected = fund_schedule(&before, NUM_MINT, FUND_AMOUNT, mode, now(&mut context).await).unwrap();
+        let pool_state = pool.state(&mut context).await;
+        assert_eq!((pool_state.reward_rate_per_token, pool_state.reward_duration_end), expected);
+    }
+}
@@ -0,0 +1,221 @@
+use anchor_lang::prelude::*;
+use crate::{CountBonus, FundMode};
+
+#[event]
+pub struct PoolInitialized {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub config: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub num_mint: u32,
+    pub reward_duration: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PoolPaused {
+    pub pool: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PoolResumed {
+    pub pool: Pubkey,
+    /// Length of the pause that just ended
+    pub paused_interval: u64,
+    /// Total time spent paused
+    pub paused_duration: u64,
+    pub reward_duration_end: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct LoyaltyRampSet {
+    pub pool: Pubkey,
+    pub loyalty_step: u64,
+    pub loyalty_step_bps: u32,
+    pub loyalty_max_bps: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CountBonusSet {
+    pub pool: Pubkey,
+    pub rules: Vec<CountBonus>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MintGroupAdded {
+    pub pool: Pubkey,
+    pub index: u32,
+    pub bonus_bps: u32,
+    pub mint_count: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MintGroupsCleared {
+    pub pool: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct FunderAuthorized {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub cap: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct FunderCapSet {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub cap: u64,
+    pub funded: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct FunderDeauthorized {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Funded {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub mode: FundMode,
+    pub reward_rate_per_token: u128,
+    pub reward_duration_end: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct FundPreviewed {
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub mode: FundMode,
+    pub reward_rate_per_token: u128,
+    pub reward_duration_end: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Donated {
+    pub pool: Pubkey,
+    pub donor: Pubkey,
+    pub amount: u64,
+    pub reward_duration_end: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UserCreated {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub user_count: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Staked {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    /// Number of tokens staked by the instruction
+    pub count: u32,
+    pub mint_staked_count: u32,
+    pub token_stake_count: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Unstaked {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    /// Number of tokens unstaked by the instruction
+    pub count: u32,
+    pub mint_staked_count: u32,
+    pub token_stake_count: u32,
+    /// Unstaked through emergency_unstake, accrual since the last settlement was forfeited
+    pub emergency: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Claimed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub reward_to_account: Pubkey,
+    pub amount: u64,
+    pub reward_owed: u64,
+    pub reward_earned_claimed: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ClaimDelegateSet {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    /// Signer allowed to call claim_for, Pubkey::default() disables claim_for
+    pub delegate: Pubkey,
+    /// Reward token account claim_for may pay into besides the user token accounts, Pubkey::default() for none
+    pub recipient: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PendingRewardsView {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    /// Pending reward including the accrual up to `timestamp`
+    pub reward_earned_pending: u64,
+    pub reward_owed: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UserClosed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub user_count: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PoolClosed {
+    pub pool: Pubkey,
+    /// Reward tokens refunded from the reward vault to reward_refundee, funder refunds excluded
+    pub refunded: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct FunderRefunded {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub funded: u64,
+    pub refunded: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MigrationStarted {
+    /// Pool or user account closed until its migration completes
+    pub account: Pubkey,
+    pub migration: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct AccountMigrated {
+    /// Pool or user account recreated with the current layout
+    pub account: Pubkey,
+    pub version: u8,
+    pub timestamp: u64,
+}
@@ -1,19 +1,23 @@
+#![allow(clippy::bool_comparison)]
+
+pub mod events;
 pub mod utils;
 
 use std::cell::Ref;
-use std::cmp;
 use crate::constants::*;
+use crate::events::*;
 use anchor_lang::prelude::*;
 use anchor_spl::token::{TokenAccount};
-use anchor_lang::solana_program::{clock, log::sol_log};
+use anchor_lang::solana_program::clock;
 use std::convert::Into;
 use std::convert::TryInto;
 use arrayref::array_ref;
 
-const PREFIX: &str = "nft_staking";
-const PREFIX_USER: &str = "nft_staking_user";
-const PREFIX_MINT: &str = "nft_staking_mint";
-const PRECISION: u128 = u64::MAX as u128;
+pub const PREFIX: &str = "nft_staking";
+pub const PREFIX_USER: &str = "nft_staking_user";
+pub const PREFIX_MINT: &str = "nft_staking_mint";
+pub const PREFIX_MIGRATION: &str = "nft_staking_migration";
+pub const PREFIX_FUNDER: &str = "nft_staking_funder";
 
 declare_id!("paramKFFuRPLVXZWjDRbnk5xKemduYZUW2BqUp7xZD3");
 
@@ -23,14 +27,24 @@ pub mod constants {
     pub const MAX_MINT_LIMIT: usize = 300000;
 
     pub const PUBKEY_SIZE: usize = 32;
+
+    pub const MAX_COUNT_BONUS: usize = 4;
+
+    // layout version of the Pool, User and MintStaked accounts, accounts created before versioning read as 0
+    pub const ACCOUNT_VERSION: u8 = 2;
+
+    // first version with the reward per token accumulators, older users have no checkpoint of them
+    pub const REWARD_PER_TOKEN_VERSION: u8 = 2;
 }
 
 pub fn get_config_count(data: &Ref<&mut [u8]>) -> core::result::Result<usize, ProgramError> {
-    return Ok(u32::from_le_bytes(*array_ref![data, CONFIG_SIZE_START, 4]) as usize);
+    Ok(u32::from_le_bytes(*array_ref![data, CONFIG_SIZE_START, 4]) as usize)
 }
 
-pub fn check_mint_address(data: &Ref<&mut [u8]>, mint_address: &[u8; 32]) -> core::result::Result<bool, ProgramError> {
+pub fn check_mint_address(data: &Ref<&mut [u8]>, mint_address: &[u8; 32], num_mint: u32) -> core::result::Result<bool, ProgramError> {
     let mut position = CONFIG_SIZE_START + 4;
+    // the mint groups follow the mint addresses
+    let end = std::cmp::min(data.len(), mint_groups_position(num_mint));
     msg!("begin check");
     loop {
         let current_mint_address = &data[position..position + PUBKEY_SIZE];
@@ -46,12 +60,26 @@ pub fn check_mint_address(data: &Ref<&mut [u8]>, mint_address: &[u8; 32]) -> cor
             msg!("Mint address found in position {}", position);
             return Ok(true);
         }
-        position = position + PUBKEY_SIZE;
-        if position >= data.len() {
+        position += PUBKEY_SIZE;
+        if position >= end {
             break;
         }
     }
-    return Ok(false);
+    Ok(false)
+}
+
+// mint groups are stored in the config after the mint addresses as a serialized Vec<MintGroup>
+// configs created without room for them have no groups
+pub fn mint_groups_position(num_mint: u32) -> usize {
+    CONFIG_SIZE_START + 4 + PUBKEY_SIZE * num_mint as usize
+}
+
+pub fn get_mint_groups(data: &[u8], num_mint: u32) -> core::result::Result<Vec<MintGroup>, ProgramError> {
+    let position = mint_groups_position(num_mint);
+    if data.len() < position + 4 {
+        return Ok(vec![]);
+    }
+    Ok(Vec::<MintGroup>::deserialize(&mut &data[position..])?)
 }
 
 #[program]
@@ -65,7 +93,7 @@ pub mod nft_staking {
         ctx: Context<InitializePool>,
         _pool_bump: u8, uuid: String, num_mint: u32, _reward_bump: u8, reward_duration: u64,
     ) -> ProgramResult {
-        if num_mint <= 0 {
+        if num_mint == 0 {
             return Err(ErrorCode::InsufficientTokenStake.into());
         }
         if reward_duration < MIN_DURATION {
@@ -90,12 +118,26 @@ pub mod nft_staking {
         pool_account.reward_duration_end = 0;
         pool_account.token_stake_count = 0;
         pool_account.user_count = 0;
+        pool_account.paused_at = pool_account.last_update_time;
+        pool_account.paused_duration = 0;
+        pool_account.version = ACCOUNT_VERSION;
 
         let config = &mut ctx.accounts.config;
         config.authority = *ctx.accounts.authority.key;
         config.uuid = uuid;
         config.num_mint = num_mint;
 
+        emit!(PoolInitialized {
+            pool: ctx.accounts.pool_account.key(),
+            authority: ctx.accounts.pool_account.authority,
+            config: ctx.accounts.pool_account.config,
+            reward_mint: ctx.accounts.pool_account.reward_mint,
+            reward_vault: ctx.accounts.pool_account.reward_vault,
+            num_mint,
+            reward_duration,
+            timestamp: ctx.accounts.pool_account.last_update_time,
+        });
+
         Ok(())
     }
 
@@ -117,9 +159,13 @@ pub mod nft_staking {
         if index > config.num_mint - 1 {
             return Err(ErrorCode::IndexGreaterThanLength.into());
         }
+        // mints past num_mint would overwrite the mint groups stored after the mint addresses
+        if index as usize + mint_addresses.len() > config.num_mint as usize {
+            return Err(ErrorCode::IndexGreaterThanLength.into());
+        }
 
         for line in &mint_addresses {
-            let address = line.clone();
+            let address = *line;
             fixed_config_lines.push(address)
         }
 
@@ -144,102 +190,311 @@ pub mod nft_staking {
         Ok(())
     }
 
+    // count bonus rules, a user with at least `min_count` nfts staked earns `bonus_bps` more on all of them
+    // the best rule reached applies, rules replace the previous ones and apply to users from their next stake or unstake
+    // a set bonus is capped at the highest one the current rules pay, the reserve fund keeps only covers that much
+    pub fn set_count_bonus(ctx: Context<Pause>, rules: Vec<CountBonus>) -> ProgramResult {
+        if rules.len() > MAX_COUNT_BONUS {
+            return Err(ErrorCode::InvalidBonusRule.into());
+        }
+        let pool_account = &mut ctx.accounts.pool_account;
+        // the time so far accrues with the old rules
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        update_reward_per_token(pool_account, now);
+        let old_bonus_bps = max_bonus_bps(pool_account);
+        pool_account.count_bonus = Default::default();
+        for (index, rule) in rules.iter().enumerate() {
+            if rule.min_count == 0 || rule.bonus_bps == 0 {
+                return Err(ErrorCode::InvalidBonusRule.into());
+            }
+            pool_account.count_bonus[index] = *rule;
+        }
+        // the rate of the running period is rescaled so its reserve still covers the highest bonus
+        pool_account.reward_rate_per_token = rescaled_reward_rate(pool_account, old_bonus_bps);
+        emit!(CountBonusSet {
+            pool: pool_account.key(),
+            rules,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    // add a group of config mints, a user staking all of them earns `bonus_bps` more on all staked nfts
+    // the config needs room for the groups after the mint addresses, it is allocated when the config account is created
+    pub fn add_mint_group(ctx: Context<AddMintAddresses>, bonus_bps: u32, mints: Vec<Pubkey>) -> ProgramResult {
+        if bonus_bps == 0 || mints.is_empty() {
+            return Err(ErrorCode::InvalidBonusRule.into());
+        }
+        let num_mint = ctx.accounts.config.num_mint;
+        let account = ctx.accounts.config.to_account_info();
+        for mint in &mints {
+            if !check_mint_address(&account.data.borrow(), &mint.to_bytes(), num_mint)? {
+                return Err(ErrorCode::InvalidMint.into());
+            }
+        }
+
+        let mut mint_groups = get_mint_groups(&account.data.borrow(), num_mint)?;
+        mint_groups.push(MintGroup { bonus_bps, mints });
+        let serialized = mint_groups.try_to_vec()?;
+        let position = mint_groups_position(num_mint);
+        let mut data = account.data.borrow_mut();
+        if data.len() < position + serialized.len() {
+            return Err(ErrorCode::MintGroupsFull.into());
+        }
+        data[position..position + serialized.len()].copy_from_slice(&serialized);
+
+        let pool_account = &mut ctx.accounts.pool_account;
+        // the time so far accrues with the old rules
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        update_reward_per_token(pool_account, now);
+        let old_bonus_bps = max_bonus_bps(pool_account);
+        pool_account.mint_group_bonus_bps = pool_account.mint_group_bonus_bps.checked_add(bonus_bps).unwrap();
+        pool_account.reward_rate_per_token = rescaled_reward_rate(pool_account, old_bonus_bps);
+
+        emit!(MintGroupAdded {
+            pool: ctx.accounts.pool_account.key(),
+            index: (mint_groups.len() - 1) as u32,
+            bonus_bps,
+            mint_count: mint_groups[mint_groups.len() - 1].mints.len() as u32,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    // remove every mint group, users keep their set bonus until their next stake or unstake, capped at the best count rule
+    pub fn clear_mint_groups(ctx: Context<AddMintAddresses>) -> ProgramResult {
+        let num_mint = ctx.accounts.config.num_mint;
+        let account = ctx.accounts.config.to_account_info();
+        let position = mint_groups_position(num_mint);
+        let mut data = account.data.borrow_mut();
+        if data.len() >= position + 4 {
+            data[position..position + 4].copy_from_slice(&0u32.to_le_bytes());
+        }
+
+        let pool_account = &mut ctx.accounts.pool_account;
+        // the time so far accrues with the old rules
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        update_reward_per_token(pool_account, now);
+        let old_bonus_bps = max_bonus_bps(pool_account);
+        pool_account.mint_group_bonus_bps = 0;
+        pool_account.reward_rate_per_token = rescaled_reward_rate(pool_account, old_bonus_bps);
+
+        emit!(MintGroupsCleared {
+            pool: ctx.accounts.pool_account.key(),
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    // pause stops reward accrual from now on until the pool is resumed
     pub fn pause(ctx: Context<Pause>) -> ProgramResult {
         let pool_account = &mut ctx.accounts.pool_account;
+        if pool_account.paused {
+            return Err(ErrorCode::PoolPaused.into());
+        }
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        update_reward_per_token(pool_account, now);
         pool_account.paused = true;
+        pool_account.paused_at = now;
+        emit!(PoolPaused {
+            pool: pool_account.key(),
+            timestamp: now,
+        });
+
         Ok(())
     }
 
+    // resume shifts the reward period end by the paused interval so a pause is economically neutral
     pub fn resume(ctx: Context<Pause>) -> ProgramResult {
         let pool_account = &mut ctx.accounts.pool_account;
+        if !pool_account.paused {
+            return Err(ErrorCode::PoolNotPaused.into());
+        }
+        let now: u64 = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        // nothing accrued while paused, the accumulators restart from now
+        update_reward_per_token(pool_account, now);
+        let paused_interval = now.checked_sub(pool_account.paused_at).unwrap();
+        pool_account.paused_duration = pool_account.paused_duration.checked_add(paused_interval).unwrap();
+        // only a reward period that was still running when paused is extended
+        if pool_account.reward_duration_end > pool_account.paused_at {
+            pool_account.reward_duration_end = pool_account.reward_duration_end.checked_add(paused_interval).unwrap();
+        } else {
+            pool_account.paused_after_end = pool_account.paused_after_end.checked_add(paused_interval).unwrap();
+        }
         pool_account.paused = false;
+        emit!(PoolResumed {
+            pool: pool_account.key(),
+            paused_interval,
+            paused_duration: pool_account.paused_duration,
+            reward_duration_end: pool_account.reward_duration_end,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    // loyalty ramp: every `loyalty_step` seconds an nft stays staked adds `loyalty_step_bps` to its earning weight, up to `loyalty_max_bps`
+    // the bonus is paid from the reserve fund keeps for it, it applies to the unsettled stake time of every user
+    // up to the reserve kept over that time
+    pub fn set_loyalty_ramp(ctx: Context<Pause>, loyalty_step: u64, loyalty_step_bps: u32, loyalty_max_bps: u32) -> ProgramResult {
+        let pool_account = &mut ctx.accounts.pool_account;
+        // the time so far accrues with the old rules
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        update_reward_per_token(pool_account, now);
+        let old_bonus_bps = max_bonus_bps(pool_account);
+        pool_account.loyalty_step = loyalty_step;
+        pool_account.loyalty_step_bps = loyalty_step_bps;
+        pool_account.loyalty_max_bps = loyalty_max_bps;
+        // the rate of the running period is rescaled so its reserve still covers the highest bonus
+        pool_account.reward_rate_per_token = rescaled_reward_rate(pool_account, old_bonus_bps);
+        emit!(LoyaltyRampSet {
+            pool: pool_account.key(),
+            loyalty_step,
+            loyalty_step_bps,
+            loyalty_max_bps,
+            timestamp: now,
+        });
+
         Ok(())
     }
 
-    // add funder
-    pub fn authorize_funder(ctx: Context<FunderChange>, funder_to_add: Pubkey) -> ProgramResult {
+    // add funder, `cap` limits the total amount the funder can fund
+    // a funder funds through its funder account while it is authorized, there is no limit on the number of funders
+    pub fn authorize_funder(ctx: Context<AuthorizeFunder>, funder_bump: u8, funder_to_add: Pubkey, cap: u64) -> ProgramResult {
         // owner cannot be added into funders
         if funder_to_add == ctx.accounts.pool_account.authority {
             return Err(ErrorCode::FunderAlreadyAuthorized.into());
         }
-        let funders = &mut ctx.accounts.pool_account.funders;
-        if funders.iter().any(|x| *x == funder_to_add) {
-            return Err(ErrorCode::FunderAlreadyAuthorized.into());
-        }
-        let default_pubkey = Pubkey::default();
-        if let Some(idx) = funders.iter().position(|x| *x == default_pubkey) {
-            funders[idx] = funder_to_add;
+        let funder_info = &ctx.accounts.funder_account;
+        let pool_key = ctx.accounts.pool_account.key();
+        let funder_account = if funder_info.data_is_empty() {
+            create_pda_account(
+                &ctx.accounts.authority,
+                funder_info,
+                &ctx.accounts.system_program.to_account_info(),
+                FUNDER_SIZE,
+                ctx.program_id,
+                &[PREFIX_FUNDER.as_bytes(), pool_key.as_ref(), funder_to_add.as_ref(), &[funder_bump]],
+            )?;
+            Funder {
+                pool: pool_key,
+                funder: funder_to_add,
+                cap,
+                authorized: true,
+                ..Funder::default()
+            }
         } else {
-            return Err(ErrorCode::MaxFunders.into());
+            // a deauthorized funder keeps its account and what it funded
+            let funder_account = load_funder(ctx.program_id, funder_info, &pool_key)?;
+            if funder_account.authorized {
+                return Err(ErrorCode::FunderAlreadyAuthorized.into());
+            }
+            Funder {
+                cap,
+                authorized: true,
+                ..funder_account
+            }
+        };
+        store_funder(funder_info, &funder_account)?;
+
+        let pool_account = &mut ctx.accounts.pool_account;
+        pool_account.funder_count = pool_account.funder_count.checked_add(1).unwrap();
+        // funders of the old list have to be authorized again to get a funder account
+        if let Some(idx) = pool_account.legacy_funders.iter().position(|x| *x == funder_to_add) {
+            pool_account.legacy_funders[idx] = Pubkey::default();
         }
+
+        emit!(FunderAuthorized {
+            pool: pool_key,
+            funder: funder_to_add,
+            cap,
+            timestamp: clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+        });
+
         Ok(())
     }
 
-    // remove funder
-    pub fn deauthorize_funder(ctx: Context<FunderChange>, funder_to_remove: Pubkey) -> ProgramResult {
+    // remove funder, its funder account stays with what it funded so close_pool still refunds its share
+    pub fn deauthorize_funder(ctx: Context<DeauthorizeFunder>, funder_to_remove: Pubkey) -> ProgramResult {
         if funder_to_remove == ctx.accounts.pool_account.authority {
             return Err(ErrorCode::CannotDeauthorizePoolAuthority.into());
         }
-        let funders = &mut ctx.accounts.pool_account.funders;
-        if let Some(idx) = funders.iter().position(|x| *x == funder_to_remove) {
-            funders[idx] = Pubkey::default();
-        } else {
+        let funder_info = &ctx.accounts.funder_account;
+        if funder_info.data_is_empty() {
+            return Err(ErrorCode::CannotDeauthorizeMissingAuthority.into());
+        }
+        let pool_key = ctx.accounts.pool_account.key();
+        let mut funder_account = load_funder(ctx.program_id, funder_info, &pool_key)?;
+        if funder_account.funder != funder_to_remove {
+            return Err(ErrorCode::InvalidFunderAccount.into());
+        }
+        if !funder_account.authorized {
             return Err(ErrorCode::CannotDeauthorizeMissingAuthority.into());
         }
+
+        funder_account.authorized = false;
+        store_funder(funder_info, &funder_account)?;
+
+        let pool_account = &mut ctx.accounts.pool_account;
+        pool_account.funder_count = pool_account.funder_count.saturating_sub(1);
+        emit!(FunderDeauthorized {
+            pool: pool_key,
+            funder: funder_to_remove,
+            timestamp: clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+        });
+
+        Ok(())
+    }
+
+    // change the cap of an authorized funder, a cap below the funded amount stops further funding
+    pub fn set_funder_cap(ctx: Context<SetFunderCap>, cap: u64) -> ProgramResult {
+        let funder_account = &mut ctx.accounts.funder_account;
+        funder_account.cap = cap;
+        emit!(FunderCapSet {
+            pool: funder_account.pool,
+            funder: funder_account.funder,
+            cap,
+            funded: funder_account.funded,
+            timestamp: clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+        });
+
         Ok(())
     }
 
-    pub fn fund(ctx: Context<Fund>, amount: u64) -> ProgramResult {
+    // `mode` selects how the amount is scheduled, see FundMode
+    pub fn fund(ctx: Context<Fund>, amount: u64, mode: FundMode) -> ProgramResult {
         let pool_account = &mut ctx.accounts.pool_account;
         let nft_quantity = ctx.accounts.config.num_mint;
 
         let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
 
-        /*
-        (New funded amount + remaing amount in the pool) / Total NFT quantity / duration (seconds)
-        */
-        if now >= pool_account.reward_duration_end {
-            msg!("amount {}", amount as u128);
-            msg!("pool_account.reward_duration {}", pool_account.reward_duration as u128);
-            msg!("nft_quantity {}", nft_quantity as u128);
-
-            pool_account.reward_rate_per_token = (amount as u128)
-                .checked_mul(PRECISION)
-                .unwrap()
-                .checked_div(pool_account.reward_duration as u128)
-                .unwrap()
-                .checked_div(nft_quantity as u128)
-                .unwrap()
-                .try_into()
-                .unwrap();
-            msg!("New reward rate per token {} ", pool_account.reward_rate_per_token);
-        } else {
-            let remaining = pool_account.reward_duration_end.checked_sub(now).unwrap();
-            // remaining reward in the pool = reward rate per token * remaining time * number of token
-            let leftover = (pool_account.reward_rate_per_token as u128)
-                .checked_mul(remaining as u128)
-                .unwrap()
-                .checked_mul(nft_quantity as u128)
-                .unwrap().try_into()
-                .unwrap();
-
-            msg!("Leftover {} rewards amount in the pool", leftover);
-
-            pool_account.reward_rate_per_token = (amount as u128)
-                .checked_mul(PRECISION)
-                .unwrap()
-                .checked_add(leftover)
-                .unwrap()
-                .checked_div(pool_account.reward_duration as u128)
-                .unwrap()
-                .checked_div(nft_quantity as u128)
-                .unwrap()
-                .try_into()
-                .unwrap();
-
-            msg!("New reward rate per token {} ", pool_account.reward_rate_per_token);
+        // the pool authority is not capped, other funders fund up to the cap of their funder account
+        if *ctx.accounts.funder.key != pool_account.authority {
+            let funder_info = &ctx.accounts.funder_account;
+            let mut funder_account = load_funder(ctx.program_id, funder_info, &pool_account.key())?;
+            if funder_account.funder != *ctx.accounts.funder.key || !funder_account.authorized {
+                return Err(ErrorCode::InvalidFunderAccount.into());
+            }
+            funder_account.funded = funder_account.funded.checked_add(amount).unwrap();
+            if funder_account.funded > funder_account.cap {
+                return Err(ErrorCode::FunderCapExceeded.into());
+            }
+            store_funder(funder_info, &funder_account)?;
+            pool_account.total_funder_funded = pool_account.total_funder_funded.checked_add(amount).unwrap();
         }
 
+        let (reward_rate_per_token, reward_duration_end) = fund_schedule(pool_account, nft_quantity, amount, mode, now)?;
+        msg!("New reward rate per token {} ", reward_rate_per_token);
+        // the time so far accrues at the old rate up to the old end, the new rate only applies from now
+        update_reward_per_token(pool_account, now);
+        pool_account.reward_rate_per_token = reward_rate_per_token;
+        pool_account.reward_duration_end = reward_duration_end;
+        // every pause so far is before the new end, the time after the old end was left out of the accumulators above
+        pool_account.paused_after_end = 0;
+
         // Transfer reward tokens into the vault.
         let cpi_ctx = CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
@@ -252,8 +507,68 @@ pub mod nft_staking {
 
         anchor_spl::token::transfer(cpi_ctx, amount)?;
 
-        pool_account.last_update_time = now; // update last update time as current time
-        pool_account.reward_duration_end = now.checked_add(pool_account.reward_duration).unwrap(); // refresh the reward end period time
+        pool_account.total_funded = pool_account.total_funded.checked_add(amount).unwrap();
+        pool_account.last_fund_time = now;
+
+        emit!(Funded {
+            pool: pool_account.key(),
+            funder: ctx.accounts.funder.key(),
+            amount,
+            mode,
+            reward_rate_per_token: pool_account.reward_rate_per_token,
+            reward_duration_end: pool_account.reward_duration_end,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    // reward rate and reward duration end a fund of `amount` in `mode` would result in, nothing is transferred or written
+    // the result is logged and emitted, simulate the transaction to read it
+    pub fn preview_fund(ctx: Context<PreviewFund>, amount: u64, mode: FundMode) -> ProgramResult {
+        let pool_account = &ctx.accounts.pool_account;
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        let (reward_rate_per_token, reward_duration_end) = fund_schedule(pool_account, ctx.accounts.config.num_mint, amount, mode, now)?;
+        msg!("Preview reward rate per token {} reward duration end {}", reward_rate_per_token, reward_duration_end);
+        emit!(FundPreviewed {
+            pool: pool_account.key(),
+            amount,
+            mode,
+            reward_rate_per_token,
+            reward_duration_end,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    // add tokens to the rewards of the pool, open to any signer
+    // the reward rate is kept and the reward duration end moves out by the time the amount lasts, like a top-up fund
+    pub fn donate(ctx: Context<Donate>, amount: u64) -> ProgramResult {
+        let pool_account = &mut ctx.accounts.pool_account;
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        let (_, reward_duration_end) = fund_schedule(pool_account, ctx.accounts.config.num_mint, amount, FundMode::TopUp, now)?;
+        update_reward_per_token(pool_account, now);
+        pool_account.reward_duration_end = reward_duration_end;
+
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.donor_vault.to_account_info(),
+                to: ctx.accounts.reward_vault.to_account_info(),
+                authority: ctx.accounts.donor.to_account_info(),
+            },
+        );
+        anchor_spl::token::transfer(cpi_ctx, amount)?;
+
+        pool_account.total_donated = pool_account.total_donated.checked_add(amount).unwrap();
+        emit!(Donated {
+            pool: pool_account.key(),
+            donor: ctx.accounts.donor.key(),
+            amount,
+            reward_duration_end: pool_account.reward_duration_end,
+            timestamp: now,
+        });
 
         Ok(())
     }
@@ -265,19 +580,32 @@ pub mod nft_staking {
         user_account.user = *ctx.accounts.user.key;
         user_account.reward_earned_claimed = 0;
         user_account.reward_earned_pending = 0;
+        user_account.reward_owed = 0;
+        user_account.claim_delegate = Pubkey::default();
+        user_account.claim_recipient = Pubkey::default();
         user_account.mint_staked_count = 0;
         user_account.uuid = uuid;
         user_account.mint_staked = *ctx.accounts.mint_staked.to_account_info().key;
+        user_account.paused_duration_checkpoint = ctx.accounts.pool_account.paused_duration;
 
         let mint_staked = &mut ctx.accounts.mint_staked;
         mint_staked.pool = *ctx.accounts.pool_account.to_account_info().key;
         mint_staked.user_account = *user_account.to_account_info().key;
+        mint_staked.version = ACCOUNT_VERSION;
 
         let pool_account = &mut ctx.accounts.pool_account;
         let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
-        pool_account.last_update_time = now;
+        update_reward_per_token(pool_account, now);
+        checkpoint_reward_per_token(pool_account, &mut ctx.accounts.user_account);
         pool_account.user_count = pool_account.user_count.checked_add(1).unwrap();
 
+        emit!(UserCreated {
+            pool: pool_account.key(),
+            user: ctx.accounts.user.key(),
+            user_count: pool_account.user_count,
+            timestamp: now,
+        });
+
         Ok(())
     }
 
@@ -293,14 +621,15 @@ pub mod nft_staking {
 
         // check constraint = config.mint_addresses.iter().any(| x | * x == stake_from_account.mint)
         let stake_from_account = &mut ctx.accounts.stake_from_account;
-        if check_mint_address(&account.data.borrow(), &stake_from_account.mint.to_bytes())? == false {
+        if !check_mint_address(&account.data.borrow(), &stake_from_account.mint.to_bytes(), config.num_mint)? {
             msg!("Mint address is not stakable!");
             return Err(ErrorCode::InvalidMint.into());
         }
 
         let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
         pool_account.token_stake_count = pool_account.token_stake_count.checked_add(1).unwrap();
-        pool_account.last_update_time = now;
+        pool_account.peak_staked = std::cmp::max(pool_account.peak_staked, pool_account.token_stake_count);
+        update_reward_per_token(pool_account, now);
 
         let user_account = &mut ctx.accounts.user_account;
         let user_opt = Some(user_account);
@@ -308,6 +637,7 @@ pub mod nft_staking {
         update_rewards(
             pool_account,
             user_opt,
+            &ctx.accounts.current_mint_staked.stake_history,
         ).unwrap();
 
         // update user account
@@ -321,12 +651,14 @@ pub mod nft_staking {
             let mint_staked = &mut ctx.accounts.mint_staked;
             mint_staked.pool = *ctx.accounts.pool_account.to_account_info().key;
             mint_staked.user_account = *ctx.accounts.user_account.to_account_info().key;
+            mint_staked.version = ACCOUNT_VERSION;
             mint_staked.mint_accounts.push(ctx.accounts.stake_from_account.key());
         } else {
             // has previous data
             let mint_staked = &mut ctx.accounts.mint_staked;
             mint_staked.pool = *ctx.accounts.pool_account.to_account_info().key;
             mint_staked.user_account = *ctx.accounts.user_account.to_account_info().key;
+            mint_staked.version = ACCOUNT_VERSION;
 
             let current_mint_staked = &mut ctx.accounts.current_mint_staked;
             for mint_address in &current_mint_staked.mint_accounts {
@@ -335,6 +667,13 @@ pub mod nft_staking {
             mint_staked.mint_accounts.push(ctx.accounts.stake_from_account.key());
         }
 
+        // carry the stake history over and start the clock of the staked nft
+        let mint_staked = &mut ctx.accounts.mint_staked;
+        mint_staked.stake_history = ctx.accounts.current_mint_staked.stake_history.clone();
+        record_stake(&mut mint_staked.stake_history, ctx.accounts.stake_from_account.mint, now, ctx.accounts.pool_account.paused_duration);
+        ctx.accounts.user_account.stake_history_count = mint_staked.stake_history.len() as u32;
+        update_set_bonus(&ctx.accounts.pool_account, &ctx.accounts.config, &mut ctx.accounts.user_account, &ctx.accounts.mint_staked.stake_history)?;
+
         // Transfer token authority
         {
             let (pool_pda, pool_bump) = Pubkey::find_program_address(&[PREFIX.as_bytes(),
@@ -357,18 +696,27 @@ pub mod nft_staking {
             anchor_spl::token::set_authority(cpi_ctx, AccountOwner, Some(pool_pda))?;
         }
 
+        emit!(Staked {
+            pool: ctx.accounts.pool_account.key(),
+            user: ctx.accounts.staker.key(),
+            count: 1,
+            mint_staked_count: ctx.accounts.user_account.mint_staked_count,
+            token_stake_count: ctx.accounts.pool_account.token_stake_count,
+            timestamp: now,
+        });
+
         Ok(())
     }
 
     // unstake
-    pub fn unstake(ctx: Context<Unstake>, _mint_staked_bump: u8, uuid: String) -> ProgramResult {
+    pub fn unstake<'info>(ctx: Context<'_, '_, '_, 'info, Unstake<'info>>, _mint_staked_bump: u8, uuid: String) -> ProgramResult {
         let pool_account = &mut ctx.accounts.pool_account;
         if pool_account.paused || !pool_account.is_initialized {
             return Err(ErrorCode::PoolPaused.into());
         }
 
         let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
-        pool_account.last_update_time = now;
+        update_reward_per_token(pool_account, now);
         pool_account.token_stake_count = pool_account.token_stake_count.checked_sub(1).unwrap();
 
         let user_account = &mut ctx.accounts.user_account;
@@ -376,40 +724,193 @@ pub mod nft_staking {
         update_rewards(
             pool_account,
             user_opt,
+            &ctx.accounts.current_mint_staked.stake_history,
+        ).unwrap();
+
+        // claim pending rewards when the reward accounts are provided
+        if let Some((reward_vault, reward_to_account)) = optional_reward_accounts(&ctx.accounts.pool_account, ctx.remaining_accounts)? {
+            transfer_pending_rewards(
+                &mut ctx.accounts.pool_account,
+                &mut ctx.accounts.user_account,
+                &reward_vault,
+                reward_to_account.to_account_info(),
+                ctx.accounts.token_program.clone(),
+                ctx.program_id,
+            )?;
+        }
+
+        release_staked_nft(ctx.accounts, ctx.program_id, uuid, now, false)
+    }
+
+    // stake many, the nft token accounts to stake are passed as remaining accounts
+    pub fn stake_many<'info>(ctx: Context<'_, '_, '_, 'info, StakeMany<'info>>, _mint_staked_bump: u8, uuid: String, count: u32) -> ProgramResult {
+        let pool_account = &mut ctx.accounts.pool_account;
+        if pool_account.paused || !pool_account.is_initialized {
+            return Err(ErrorCode::PoolPaused.into());
+        }
+        if count == 0 || ctx.remaining_accounts.len() != count as usize {
+            return Err(ErrorCode::InsufficientTokenStake.into());
+        }
+        if has_duplicate_accounts(ctx.remaining_accounts) {
+            return Err(ErrorCode::DuplicateTokenAccount.into());
+        }
+
+        // check every token account holds a mint in the config
+        let mut stake_from_accounts: Vec<Account<TokenAccount>> = vec![];
+        {
+            let account = ctx.accounts.config.to_account_info();
+            let data = account.data.borrow();
+            for stake_from_info in ctx.remaining_accounts.iter() {
+                let stake_from_account: Account<TokenAccount> = Account::try_from(stake_from_info)?;
+                if !check_mint_address(&data, &stake_from_account.mint.to_bytes(), ctx.accounts.config.num_mint)? {
+                    msg!("Mint address is not stakable!");
+                    return Err(ErrorCode::InvalidMint.into());
+                }
+                stake_from_accounts.push(stake_from_account);
+            }
+        }
+
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        pool_account.token_stake_count = pool_account.token_stake_count.checked_add(count).unwrap();
+        pool_account.peak_staked = std::cmp::max(pool_account.peak_staked, pool_account.token_stake_count);
+        update_reward_per_token(pool_account, now);
+
+        let user_account = &mut ctx.accounts.user_account;
+        let user_opt = Some(user_account);
+
+        update_rewards(
+            pool_account,
+            user_opt,
+            &ctx.accounts.current_mint_staked.stake_history,
+        ).unwrap();
+
+        // update user account
+        ctx.accounts.user_account.mint_staked = *ctx.accounts.mint_staked.to_account_info().key;
+        ctx.accounts.user_account.mint_staked_count = ctx.accounts.user_account.mint_staked_count.checked_add(count).unwrap();
+        ctx.accounts.user_account.uuid = uuid;
+
+        // update mint staked
+        let mint_staked = &mut ctx.accounts.mint_staked;
+        mint_staked.pool = *ctx.accounts.pool_account.to_account_info().key;
+        mint_staked.user_account = *ctx.accounts.user_account.to_account_info().key;
+        mint_staked.version = ACCOUNT_VERSION;
+
+        let current_mint_staked = &mut ctx.accounts.current_mint_staked;
+        for mint_address in &current_mint_staked.mint_accounts {
+            mint_staked.mint_accounts.push(*mint_address);
+        }
+        for stake_from_account in &stake_from_accounts {
+            mint_staked.mint_accounts.push(stake_from_account.key());
+        }
+        mint_staked.stake_history = current_mint_staked.stake_history.clone();
+        for stake_from_account in &stake_from_accounts {
+            record_stake(&mut mint_staked.stake_history, stake_from_account.mint, now, ctx.accounts.pool_account.paused_duration);
+        }
+        ctx.accounts.user_account.stake_history_count = mint_staked.stake_history.len() as u32;
+        update_set_bonus(&ctx.accounts.pool_account, &ctx.accounts.config, &mut ctx.accounts.user_account, &ctx.accounts.mint_staked.stake_history)?;
+
+        // Transfer token authority
+        let (pool_pda, _pool_bump) = Pubkey::find_program_address(&[PREFIX.as_bytes(),
+            ctx.accounts.pool_account.authority.as_ref(),
+            ctx.accounts.pool_account.config.as_ref(),
+        ], ctx.program_id);
+        for stake_from_account in &stake_from_accounts {
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info().clone(),
+                anchor_spl::token::SetAuthority {
+                    current_authority: ctx.accounts.staker.to_account_info().clone(),
+                    account_or_mint: stake_from_account.to_account_info().clone(),
+                },
+            );
+            msg!("Calling the token program to transfer authority from staker to pool");
+            anchor_spl::token::set_authority(cpi_ctx, AccountOwner, Some(pool_pda))?;
+        }
+
+        emit!(Staked {
+            pool: ctx.accounts.pool_account.key(),
+            user: ctx.accounts.staker.key(),
+            count,
+            mint_staked_count: ctx.accounts.user_account.mint_staked_count,
+            token_stake_count: ctx.accounts.pool_account.token_stake_count,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    // unstake many, the nft token accounts to unstake are passed as remaining accounts
+    pub fn unstake_many<'info>(ctx: Context<'_, '_, '_, 'info, UnstakeMany<'info>>, _mint_staked_bump: u8, uuid: String, count: u32) -> ProgramResult {
+        let pool_account = &mut ctx.accounts.pool_account;
+        if pool_account.paused || !pool_account.is_initialized {
+            return Err(ErrorCode::PoolPaused.into());
+        }
+        if count == 0 || ctx.remaining_accounts.len() != count as usize {
+            return Err(ErrorCode::InsufficientTokenStake.into());
+        }
+        if has_duplicate_accounts(ctx.remaining_accounts) {
+            return Err(ErrorCode::DuplicateTokenAccount.into());
+        }
+
+        // check every token account is in the mint staked
+        let unstake_from_keys: Vec<Pubkey> = ctx.remaining_accounts.iter().map(|x| *x.key).collect();
+        let staked_keys = &ctx.accounts.current_mint_staked.mint_accounts;
+        if !unstake_from_keys.iter().all(|x| staked_keys.contains(x)) {
+            return Err(ErrorCode::InvalidMint.into());
+        }
+        let mut unstake_from_accounts: Vec<Account<TokenAccount>> = vec![];
+        for unstake_from_info in ctx.remaining_accounts.iter() {
+            unstake_from_accounts.push(Account::try_from(unstake_from_info)?);
+        }
+
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        update_reward_per_token(pool_account, now);
+        pool_account.token_stake_count = pool_account.token_stake_count.checked_sub(count).unwrap();
+
+        let user_account = &mut ctx.accounts.user_account;
+        let user_opt = Some(user_account);
+        update_rewards(
+            pool_account,
+            user_opt,
+            &ctx.accounts.current_mint_staked.stake_history,
         ).unwrap();
 
         ctx.accounts.user_account.mint_staked = *ctx.accounts.mint_staked.to_account_info().key;
-        ctx.accounts.user_account.mint_staked_count = ctx.accounts.user_account.mint_staked_count.checked_sub(1).unwrap();
+        ctx.accounts.user_account.mint_staked_count = ctx.accounts.user_account.mint_staked_count.checked_sub(count).unwrap();
         ctx.accounts.user_account.uuid = uuid;
 
-        // count of user_account.mint_staked must be >= 1
         let mint_staked = &mut ctx.accounts.mint_staked;
         mint_staked.pool = *ctx.accounts.pool_account.to_account_info().key;
         mint_staked.user_account = *ctx.accounts.user_account.to_account_info().key;
+        mint_staked.version = ACCOUNT_VERSION;
 
         let current_mint_staked = &mut ctx.accounts.current_mint_staked;
         for mint_address in &current_mint_staked.mint_accounts {
-            if mint_address != ctx.accounts.unstake_from_account.to_account_info().key {
+            if !unstake_from_keys.contains(mint_address) {
                 mint_staked.mint_accounts.push(*mint_address);
             }
         }
+        mint_staked.stake_history = current_mint_staked.stake_history.clone();
+        for unstake_from_account in &unstake_from_accounts {
+            record_unstake(&mut mint_staked.stake_history, &unstake_from_account.mint, now);
+        }
+        update_set_bonus(&ctx.accounts.pool_account, &ctx.accounts.config, &mut ctx.accounts.user_account, &ctx.accounts.mint_staked.stake_history)?;
 
         // Transfer token authority
-        {
-            let (_pool_pda, pool_bump) = Pubkey::find_program_address(&[PREFIX.as_bytes(),
-                ctx.accounts.pool_account.authority.as_ref(),
-                ctx.accounts.pool_account.config.as_ref(),
-            ], ctx.program_id);
-            let seeds = &[PREFIX.as_bytes(),
-                ctx.accounts.pool_account.authority.as_ref(),
-                ctx.accounts.pool_account.config.as_ref(),
-                &[pool_bump]]; // need this to sign the pda, match the authority
+        let (_pool_pda, pool_bump) = Pubkey::find_program_address(&[PREFIX.as_bytes(),
+            ctx.accounts.pool_account.authority.as_ref(),
+            ctx.accounts.pool_account.config.as_ref(),
+        ], ctx.program_id);
+        let seeds = &[PREFIX.as_bytes(),
+            ctx.accounts.pool_account.authority.as_ref(),
+            ctx.accounts.pool_account.config.as_ref(),
+            &[pool_bump]]; // need this to sign the pda, match the authority
 
+        for unstake_from_account in &unstake_from_accounts {
             let cpi_ctx = CpiContext::new(
                 ctx.accounts.token_program.to_account_info().clone(),
                 anchor_spl::token::SetAuthority {
                     current_authority: ctx.accounts.pool_account.to_account_info().clone(),
-                    account_or_mint: ctx.accounts.unstake_from_account.to_account_info().clone(),
+                    account_or_mint: unstake_from_account.to_account_info().clone(),
                 },
             );
             msg!("Calling the token program to transfer authority from pool to unstaker");
@@ -418,9 +919,35 @@ pub mod nft_staking {
                                              Some(ctx.accounts.staker.key()))?;
         }
 
+        emit!(Unstaked {
+            pool: ctx.accounts.pool_account.key(),
+            user: ctx.accounts.staker.key(),
+            count,
+            mint_staked_count: ctx.accounts.user_account.mint_staked_count,
+            token_stake_count: ctx.accounts.pool_account.token_stake_count,
+            emergency: false,
+            timestamp: now,
+        });
+
         Ok(())
     }
 
+    // emergency unstake, always available to the staker even when the pool is paused
+    // rewards accrued since the last settlement are forfeited, the pending rewards are kept
+    pub fn emergency_unstake(ctx: Context<Unstake>, _mint_staked_bump: u8, uuid: String) -> ProgramResult {
+        let pool_account = &mut ctx.accounts.pool_account;
+        pool_account.token_stake_count = pool_account.token_stake_count.checked_sub(1).unwrap();
+
+        // no reward settlement here, only move the user checkpoints forward
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        update_reward_per_token(pool_account, now);
+        ctx.accounts.user_account.last_update_time = now;
+        ctx.accounts.user_account.paused_duration_checkpoint = pool_account.paused_duration_at(now);
+        checkpoint_reward_per_token(pool_account, &mut ctx.accounts.user_account);
+
+        release_staked_nft(ctx.accounts, ctx.program_id, uuid, now, true)
+    }
+
     pub fn claim(ctx: Context<ClaimReward>) -> ProgramResult {
         let pool_account = &mut ctx.accounts.pool_account;
         if pool_account.paused || !pool_account.is_initialized {
@@ -432,84 +959,219 @@ pub mod nft_staking {
         update_rewards(
             pool_account,
             user_opt,
+            &ctx.accounts.mint_staked.stake_history,
         ).unwrap();
 
         // Transfer rewards from the pool reward vaults to user reward vaults.
-        let (_pool_pda, pool_bump) = Pubkey::find_program_address(&[PREFIX.as_bytes(),
-            ctx.accounts.pool_account.authority.as_ref(),
-            ctx.accounts.pool_account.config.as_ref(),
-        ], ctx.program_id);
-        let seeds = &[PREFIX.as_bytes(),
-            ctx.accounts.pool_account.authority.as_ref(),
-            ctx.accounts.pool_account.config.as_ref(),
-            &[pool_bump]]; // need this to sign the pda, match the authority
+        transfer_pending_rewards(
+            &mut ctx.accounts.pool_account,
+            &mut ctx.accounts.user_account,
+            &ctx.accounts.reward_vault,
+            ctx.accounts.reward_to_account.to_account_info(),
+            ctx.accounts.token_program.clone(),
+            ctx.program_id,
+        )?;
+
+        Ok(())
+    }
 
-        if ctx.accounts.user_account.reward_earned_pending > 0 {
-            let mut reward_amount = ctx.accounts.user_account.reward_earned_pending;
-            let vault_balance = ctx.accounts.reward_vault.amount;
+    // read-only view of the user pending rewards, simulate the instruction and read the emitted event
+    pub fn view_pending_rewards(ctx: Context<ViewPendingRewards>) -> ProgramResult {
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        let (reward_earned_pending, _paused_duration) = pending_rewards(
+            &ctx.accounts.pool_account,
+            &ctx.accounts.user_account,
+            &ctx.accounts.mint_staked.stake_history,
+            now,
+        );
 
-            // settle pending reward
-            ctx.accounts.user_account.reward_earned_pending = 0;
-            ctx.accounts.user_account.reward_earned_claimed = ctx.accounts.user_account.reward_earned_claimed + reward_amount;
+        emit!(PendingRewardsView {
+            pool: ctx.accounts.pool_account.key(),
+            user: ctx.accounts.user_account.user,
+            reward_earned_pending,
+            reward_owed: ctx.accounts.user_account.reward_owed,
+            timestamp: now,
+        });
 
-            if vault_balance < reward_amount {
-                reward_amount = vault_balance;
-            }
+        Ok(())
+    }
 
-            if reward_amount > 0 {
-                let token_program = ctx.accounts.token_program.clone();
-                let token_accounts = anchor_spl::token::Transfer {
-                    from: ctx.accounts.reward_vault.to_account_info().clone(),
-                    to: ctx
-                        .accounts
-                        .reward_to_account
-                        .to_account_info()
-                        .clone(),
-                    authority: ctx.accounts.pool_account.to_account_info().clone(),
-                };
-                let cpi_ctx = CpiContext::new(token_program, token_accounts);
-                msg!("Calling the token program to transfer reward {} to the user", reward_amount);
-                anchor_spl::token::transfer(
-                    cpi_ctx.with_signer(&[&seeds[..]]),
-                    reward_amount,
-                )?;
-            }
-        }
+    // set who can claim on behalf of the user and the token account that may receive the reward
+    // Pubkey::default() delegate disables claim_for, Pubkey::default() recipient allows only the user token accounts
+    pub fn set_claim_delegate(ctx: Context<SetClaimDelegate>, delegate: Pubkey, recipient: Pubkey) -> ProgramResult {
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.claim_delegate = delegate;
+        user_account.claim_recipient = recipient;
+        emit!(ClaimDelegateSet {
+            pool: ctx.accounts.pool_account.key(),
+            user: user_account.user,
+            delegate,
+            recipient,
+            timestamp: clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+        });
 
         Ok(())
     }
 
-    pub fn close_user(ctx: Context<CloseUser>) -> ProgramResult {
+    // claim on behalf of the user into the user token account or the recipient registered by the user
+    pub fn claim_for(ctx: Context<ClaimFor>) -> ProgramResult {
         let pool_account = &mut ctx.accounts.pool_account;
         if pool_account.paused || !pool_account.is_initialized {
             return Err(ErrorCode::PoolPaused.into());
         }
 
-        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
-        pool_account.last_update_time = now;
-        pool_account.user_count = pool_account.user_count.checked_sub(1).unwrap();
-
         let user_account = &mut ctx.accounts.user_account;
         let user_opt = Some(user_account);
         update_rewards(
             pool_account,
             user_opt,
+            &ctx.accounts.mint_staked.stake_history,
         ).unwrap();
 
-        if ctx.accounts.user_account.mint_staked_count > 0 {
-            return Err(ErrorCode::StakedMint.into());
+        // Transfer rewards from the pool reward vaults to the user designated account.
+        transfer_pending_rewards(
+            &mut ctx.accounts.pool_account,
+            &mut ctx.accounts.user_account,
+            &ctx.accounts.reward_vault,
+            ctx.accounts.reward_to_account.to_account_info(),
+            ctx.accounts.token_program.clone(),
+            ctx.program_id,
+        )?;
+
+        Ok(())
+    }
+
+    // claim into the user associated token account for the reward mint, creating it if needed
+    pub fn claim_to_associated_token(ctx: Context<ClaimRewardToAssociated>) -> ProgramResult {
+        let (associated_token, _bump) = Pubkey::find_program_address(&[
+            ctx.accounts.user.key.as_ref(),
+            ctx.accounts.token_program.key.as_ref(),
+            ctx.accounts.reward_mint.key.as_ref(),
+        ], &anchor_spl::associated_token::ID);
+        if associated_token != *ctx.accounts.reward_to_account.key {
+            return Err(ErrorCode::InvalidRewardAccount.into());
         }
 
-        if ctx.accounts.user_account.reward_earned_pending > 0 {
-            return Err(ErrorCode::PendingRewards.into());
+        if ctx.accounts.reward_to_account.data_is_empty() {
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.associated_token_program.clone(),
+                anchor_spl::associated_token::Create {
+                    payer: ctx.accounts.user.clone(),
+                    associated_token: ctx.accounts.reward_to_account.clone(),
+                    authority: ctx.accounts.user.clone(),
+                    mint: ctx.accounts.reward_mint.clone(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.clone(),
+                    rent: ctx.accounts.rent.to_account_info(),
+                },
+            );
+            msg!("Calling the associated token program to create the user reward account");
+            anchor_spl::associated_token::create(cpi_ctx)?;
         }
 
-        // ok
+        let user_account = &mut ctx.accounts.user_account;
+        update_rewards(
+            &mut ctx.accounts.pool_account,
+            Some(user_account),
+            &ctx.accounts.mint_staked.stake_history,
+        ).unwrap();
+
+        // Transfer rewards from the pool reward vaults to the user associated token account.
+        transfer_pending_rewards(
+            &mut ctx.accounts.pool_account,
+            &mut ctx.accounts.user_account,
+            &ctx.accounts.reward_vault,
+            ctx.accounts.reward_to_account.clone(),
+            ctx.accounts.token_program.clone(),
+            ctx.program_id,
+        )?;
+
         Ok(())
     }
 
-    pub fn close_pool(ctx: Context<ClosePool>) -> ProgramResult {
+    pub fn close_user<'info>(ctx: Context<'_, '_, '_, 'info, CloseUser<'info>>) -> ProgramResult {
+        let pool_account = &mut ctx.accounts.pool_account;
+        if pool_account.paused || !pool_account.is_initialized {
+            return Err(ErrorCode::PoolPaused.into());
+        }
+
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        update_reward_per_token(pool_account, now);
+        pool_account.user_count = pool_account.user_count.checked_sub(1).unwrap();
+
+        let user_account = &mut ctx.accounts.user_account;
+        let user_opt = Some(user_account);
+        update_rewards(
+            pool_account,
+            user_opt,
+            &ctx.accounts.mint_staked.stake_history,
+        ).unwrap();
+
+        if ctx.accounts.user_account.mint_staked_count > 0 {
+            return Err(ErrorCode::StakedMint.into());
+        }
+
+        // claim pending rewards when the reward accounts and the token program are provided
+        if let Some((reward_vault, reward_to_account)) = optional_reward_accounts(&ctx.accounts.pool_account, ctx.remaining_accounts)? {
+            let token_program = ctx.remaining_accounts.get(2).ok_or(ErrorCode::InvalidRewardAccount)?;
+            if *token_program.key != spl_token::id() {
+                return Err(ErrorCode::InvalidRewardAccount.into());
+            }
+            transfer_pending_rewards(
+                &mut ctx.accounts.pool_account,
+                &mut ctx.accounts.user_account,
+                &reward_vault,
+                reward_to_account.to_account_info(),
+                token_program.clone(),
+                ctx.program_id,
+            )?;
+        }
+
+        if ctx.accounts.user_account.reward_earned_pending > 0 || ctx.accounts.user_account.reward_owed > 0 {
+            return Err(ErrorCode::PendingRewards.into());
+        }
+
+        emit!(UserClosed {
+            pool: ctx.accounts.pool_account.key(),
+            user: ctx.accounts.user.key(),
+            user_count: ctx.accounts.pool_account.user_count,
+            timestamp: now,
+        });
+
+        // ok
+        Ok(())
+    }
+
+    // close a mint staked account that is no longer referenced by the user account, refund the rent to the user
+    pub fn close_mint_staked(ctx: Context<CloseMintStaked>) -> ProgramResult {
+        let (user_pda, _user_bump) = Pubkey::find_program_address(&[PREFIX_USER.as_bytes(),
+            ctx.accounts.pool_account.to_account_info().key.as_ref(),
+            ctx.accounts.user.key.as_ref(),
+        ], ctx.program_id);
+        if user_pda != *ctx.accounts.user_account.key {
+            return Err(ErrorCode::InvalidUserAccount.into());
+        }
+
+        // a live user account must not reference the mint staked account
+        let user_account_info = &ctx.accounts.user_account;
+        if user_account_info.owner == ctx.program_id && !user_account_info.data_is_empty() {
+            let user_account: ProgramAccount<User> = ProgramAccount::try_from(ctx.program_id, user_account_info)?;
+            if user_account.mint_staked == *ctx.accounts.mint_staked.to_account_info().key {
+                return Err(ErrorCode::MintStakedInUse.into());
+            }
+        }
+
+        msg!("Closing orphaned mint staked account");
+        Ok(())
+    }
+
+    // remaining accounts are pairs of funder account and reward token account of the funder,
+    // each funder passed gets the share of the reward vault matching its share of total_funded and its funder account is closed,
+    // the rest of the vault goes to reward_refundee
+    pub fn close_pool<'info>(ctx: Context<'_, '_, '_, 'info, ClosePool<'info>>) -> ProgramResult {
         // let pool_account = &mut ctx.accounts.pool_account;
+        let vault_amount = ctx.accounts.reward_vault.amount;
+        let mut refunded = vault_amount;
 
         let (_pool_pda, pool_bump) = Pubkey::find_program_address(&[PREFIX.as_bytes(),
             ctx.accounts.pool_account.authority.as_ref(),
@@ -520,6 +1182,45 @@ pub mod nft_staking {
             ctx.accounts.pool_account.config.as_ref(),
             &[pool_bump]]; // need this to sign the pda, match the authority
 
+        if ctx.remaining_accounts.len() % 2 == 1 {
+            return Err(ErrorCode::InvalidFunderAccount.into());
+        }
+        let pool_key = ctx.accounts.pool_account.key();
+        let now = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+        let mut funder_infos: Vec<&AccountInfo> = vec![];
+        for accounts in ctx.remaining_accounts.chunks(2) {
+            let funder_info = &accounts[0];
+            if funder_infos.iter().any(|x| x.key == funder_info.key) {
+                return Err(ErrorCode::InvalidFunderAccount.into());
+            }
+            let funder_account = load_funder(ctx.program_id, funder_info, &pool_key)?;
+            let funder_vault: Account<TokenAccount> = Account::try_from(&accounts[1])?;
+            if funder_vault.owner != funder_account.funder || funder_vault.mint != ctx.accounts.pool_account.reward_mint {
+                return Err(ErrorCode::InvalidRewardAccount.into());
+            }
+
+            let share = std::cmp::min(funder_refund(&ctx.accounts.pool_account, vault_amount, funder_account.funded), refunded);
+            if share > 0 {
+                let token_accounts = anchor_spl::token::Transfer {
+                    from: ctx.accounts.reward_vault.to_account_info(),
+                    to: funder_vault.to_account_info(),
+                    authority: ctx.accounts.pool_account.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(ctx.accounts.token_program.clone(), token_accounts);
+                anchor_spl::token::transfer(cpi_ctx.with_signer(&[&seeds[..]]), share)?;
+                refunded -= share;
+            }
+            funder_infos.push(funder_info);
+
+            emit!(FunderRefunded {
+                pool: pool_key,
+                funder: funder_account.funder,
+                funded: funder_account.funded,
+                refunded: share,
+                timestamp: now,
+            });
+        }
+
         //close reward vault
         let token_program = ctx.accounts.token_program.clone();
         let token_accounts = anchor_spl::token::Transfer {
@@ -531,7 +1232,7 @@ pub mod nft_staking {
         msg!("Calling the token program to refund reward");
         anchor_spl::token::transfer(
             cpi_ctx.with_signer(&[&seeds[..]]),
-            ctx.accounts.reward_vault.amount,
+            refunded,
         )?;
 
         let token_program = ctx.accounts.token_program.clone();
@@ -546,6 +1247,136 @@ pub mod nft_staking {
             cpi_ctx.with_signer(&[&seeds[..]]),
         )?;
 
+        // close the refunded funder accounts after the token program calls, the rent goes to the refundee
+        for funder_info in funder_infos {
+            close_account_info(funder_info, &ctx.accounts.refundee)?;
+        }
+
+        emit!(PoolClosed {
+            pool: pool_key,
+            refunded,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    // first step of moving a pool created with an older layout to the current one
+    // the runtime can't resize the account, so its data goes into a migration account and the pool is closed,
+    // migrate_pool recreates it at the same address in a later transaction
+    // the reward vault, the config and the user accounts are untouched
+    pub fn begin_migrate_pool(ctx: Context<BeginMigratePool>, _migration_bump: u8) -> ProgramResult {
+        let pool_info = &ctx.accounts.pool_account;
+        if pool_info.owner != ctx.program_id {
+            return Err(ProgramError::IllegalOwner);
+        }
+        let data = pool_info.try_borrow_data()?.to_vec();
+        let pool: Pool = read_older_layout(&data, POOL_SIZE)?;
+        if pool.authority != *ctx.accounts.authority.key {
+            return Err(anchor_lang::__private::ErrorCode::ConstraintHasOne.into());
+        }
+
+        let migration = &mut ctx.accounts.migration;
+        migration.account = *pool_info.key;
+        migration.data = data;
+        close_account_info(pool_info, &ctx.accounts.authority)?;
+
+        emit!(MigrationStarted {
+            account: *pool_info.key,
+            migration: migration.key(),
+            timestamp: clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+        });
+        Ok(())
+    }
+
+    // recreate the pool closed by begin_migrate_pool with the current layout
+    pub fn migrate_pool(ctx: Context<MigratePool>, _pool_bump: u8) -> ProgramResult {
+        let mut pool: Pool = read_older_layout(&ctx.accounts.migration.data, POOL_SIZE)?;
+        if pool.authority != *ctx.accounts.authority.key || pool.config != ctx.accounts.config.key() {
+            return Err(ErrorCode::InvalidMigration.into());
+        }
+        // layouts before the version field don't track pauses, a paused pool counts its pause from the migration
+        if pool.version == 0 && pool.paused {
+            pool.paused_at = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
+            pool.paused_duration = 0;
+        }
+        pool.version = ACCOUNT_VERSION;
+        *ctx.accounts.pool_account = pool;
+
+        emit!(AccountMigrated {
+            account: ctx.accounts.pool_account.key(),
+            version: ACCOUNT_VERSION,
+            timestamp: clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+        });
+        Ok(())
+    }
+
+    // first step of moving a user account created with an older layout to the current one, see begin_migrate_pool
+    // the pool has to be migrated first
+    pub fn begin_migrate_user(ctx: Context<BeginMigrateUser>, _migration_bump: u8) -> ProgramResult {
+        let user_info = &ctx.accounts.user_account;
+        if user_info.owner != ctx.program_id {
+            return Err(ProgramError::IllegalOwner);
+        }
+        let data = user_info.try_borrow_data()?.to_vec();
+        let user: User = read_older_layout(&data, USER_SIZE)?;
+        if user.user != *ctx.accounts.user.key || user.pool != ctx.accounts.pool_account.key() {
+            return Err(ErrorCode::InvalidUserAccount.into());
+        }
+
+        let migration = &mut ctx.accounts.migration;
+        migration.account = *user_info.key;
+        migration.data = data;
+        close_account_info(user_info, &ctx.accounts.user)?;
+
+        emit!(MigrationStarted {
+            account: *user_info.key,
+            migration: migration.key(),
+            timestamp: clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+        });
+        Ok(())
+    }
+
+    // recreate the user account closed by begin_migrate_user with the current layout
+    // the mint staked account is moved to a new one with the current layout as well
+    pub fn migrate_user(ctx: Context<MigrateUser>, _user_bump: u8, _mint_staked_bump: u8, uuid: String) -> ProgramResult {
+        let mut user: User = read_older_layout(&ctx.accounts.migration.data, USER_SIZE)?;
+        if user.user != *ctx.accounts.user.key || user.pool != ctx.accounts.pool_account.key() {
+            return Err(ErrorCode::InvalidMigration.into());
+        }
+
+        // mint staked account on the older layout
+        let current_mint_staked_info = &ctx.accounts.current_mint_staked;
+        if current_mint_staked_info.owner != ctx.program_id || *current_mint_staked_info.key != user.mint_staked {
+            return Err(ErrorCode::InvalidMigration.into());
+        }
+        let current_mint_staked: MintStaked = read_older_layout(
+            &current_mint_staked_info.try_borrow_data()?,
+            current_mint_staked_info.data_len() + 1 + 4,
+        )?;
+        if current_mint_staked.mint_accounts.len() != user.mint_staked_count as usize {
+            return Err(ErrorCode::InvalidMigration.into());
+        }
+
+        let mint_staked = &mut ctx.accounts.mint_staked;
+        mint_staked.pool = ctx.accounts.pool_account.key();
+        mint_staked.user_account = ctx.accounts.user_account.key();
+        mint_staked.version = ACCOUNT_VERSION;
+        mint_staked.mint_accounts = current_mint_staked.mint_accounts;
+        close_account_info(current_mint_staked_info, &ctx.accounts.user)?;
+
+        user.uuid = uuid;
+        user.mint_staked = mint_staked.key();
+        *ctx.accounts.user_account = user;
+        // settle the stake time since the last update of the older layout, it has no reward per token checkpoint
+        // this moves the user to the current version
+        update_rewards(&mut ctx.accounts.pool_account, Some(&mut ctx.accounts.user_account), &[])?;
+
+        emit!(AccountMigrated {
+            account: ctx.accounts.user_account.key(),
+            version: ACCOUNT_VERSION,
+            timestamp: clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+        });
         Ok(())
     }
 }
@@ -622,12 +1453,32 @@ pub struct Pause<'info> {
     // Pool Account
     #[account(mut,
     constraint = pool_account.is_initialized == true,
+    has_one = authority,
+    )]
+    pool_account: ProgramAccount<'info, Pool>,
+}
+
+#[derive(Accounts)]
+pub struct DeauthorizeFunder<'info> {
+    #[account(mut, signer)]
+    authority: AccountInfo<'info>,
+
+    // Pool Account
+    #[account(mut,
+    constraint = pool_account.is_initialized == true,
+    has_one = authority,
     )]
     pool_account: ProgramAccount<'info, Pool>,
+
+    // funder account, deauthorized in process
+    // verify pool and funder in process
+    #[account(mut)]
+    funder_account: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
-pub struct FunderChange<'info> {
+#[instruction(funder_bump: u8, funder_to_add: Pubkey)]
+pub struct AuthorizeFunder<'info> {
     #[account(mut, signer)]
     authority: AccountInfo<'info>,
 
@@ -637,14 +1488,39 @@ pub struct FunderChange<'info> {
     has_one = authority,
     )]
     pool_account: ProgramAccount<'info, Pool>,
+
+    // funder account, created in process
+    #[account(mut,
+    seeds = [PREFIX_FUNDER.as_bytes(), pool_account.key().as_ref(), funder_to_add.as_ref()],
+    bump = funder_bump)]
+    funder_account: AccountInfo<'info>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetFunderCap<'info> {
+    #[account(signer)]
+    authority: AccountInfo<'info>,
+
+    // Pool Account
+    #[account(
+    constraint = pool_account.is_initialized == true,
+    has_one = authority,
+    )]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+    constraint = funder_account.pool == pool_account.key(),
+    )]
+    funder_account: ProgramAccount<'info, Funder>,
 }
 
 #[derive(Accounts)]
 pub struct Fund<'info> {
     // funder
-    // verify in the funders list
-    #[account(mut, signer,
-    constraint = funder.key() == pool_account.authority || pool_account.funders.iter().any(| x | * x == funder.key()),)]
+    // verify the pool authority or a funder with a funder account in process
+    #[account(mut, signer)]
     funder: AccountInfo<'info>,
 
     // Pool owner
@@ -667,19 +1543,77 @@ pub struct Fund<'info> {
     #[account(has_one = authority)]
     config: ProgramAccount<'info, Config>,
 
-    #[account(mut)]
+    #[account(mut,
+    constraint = reward_vault.mint == pool_account.reward_mint,
+    )]
     reward_vault: Box<Account<'info, TokenAccount>>,
 
     // funder vault
-    #[account(mut)]
+    #[account(mut,
+    constraint = funder_vault.mint == pool_account.reward_mint,
+    )]
     funder_vault: Account<'info, TokenAccount>,
 
+    // funder account, not used when the pool authority funds
+    // verify it exists and matches the pool and the funder in process
+    #[account(mut)]
+    funder_account: AccountInfo<'info>,
+
     // The Token Program
     #[account(address = spl_token::id())]
     token_program: AccountInfo<'info>,
 
 }
 
+#[derive(Accounts)]
+pub struct PreviewFund<'info> {
+    // Pool Account
+    #[account(
+    constraint = pool_account.is_initialized == true,
+    constraint = pool_account.config == * config.to_account_info().key,
+    )]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // the config account
+    config: ProgramAccount<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct Donate<'info> {
+    // any signer
+    #[account(signer)]
+    donor: AccountInfo<'info>,
+
+    // Pool Account
+    // verify pool is not paused
+    // verify token vault
+    #[account(mut,
+    constraint = pool_account.is_initialized == true,
+    constraint = pool_account.paused == false,
+    constraint = pool_account.reward_vault == * reward_vault.to_account_info().key,
+    constraint = pool_account.config == * config.to_account_info().key,
+    )]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // the config account
+    config: ProgramAccount<'info, Config>,
+
+    #[account(mut,
+    constraint = reward_vault.mint == pool_account.reward_mint,
+    )]
+    reward_vault: Box<Account<'info, TokenAccount>>,
+
+    // donor vault
+    #[account(mut,
+    constraint = donor_vault.mint == pool_account.reward_mint,
+    )]
+    donor_vault: Account<'info, TokenAccount>,
+
+    // The Token Program
+    #[account(address = spl_token::id())]
+    token_program: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 #[instruction(user_bump: u8, mint_staked_bump: u8, uuid: String)]
 pub struct CreateUser<'info> {
@@ -782,7 +1716,7 @@ pub struct Stake<'info> {
     uuid.as_bytes(),
     ],
     bump = mint_staked_bump,
-    space = MINT_STAKED_SIZE_START + 32 * (user_account.mint_staked_count + 1) as usize)]
+    space = MINT_STAKED_SIZE_START + 32 * (user_account.mint_staked_count + 1) as usize + STAKE_HISTORY_SIZE * (user_account.stake_history_count + 1) as usize)]
     mint_staked: ProgramAccount<'info, MintStaked>,
 
     // existing mint staked account
@@ -811,13 +1745,12 @@ pub struct Unstake<'info> {
     staker: AccountInfo<'info>,
 
     // Pool Account
-    // verify pool is not paused
+    // pool may be paused, unstake checks it in process, emergency_unstake does not
     // verify owner
     // verify config
     #[account(mut,
     has_one = authority,
     constraint = pool_account.is_initialized == true,
-    constraint = pool_account.paused == false,
     constraint = pool_account.config == * config.to_account_info().key,
     )]
     pool_account: ProgramAccount<'info, Pool>,
@@ -845,6 +1778,7 @@ pub struct Unstake<'info> {
     unstake_from_account: Box<Account<'info, TokenAccount>>,
 
     // new mint staked account to store all the mint staked for the user
+    // remaining accounts (optional): [reward_vault, reward_to_account] to claim pending rewards
     #[account(
     init,
     payer = staker,
@@ -855,7 +1789,7 @@ pub struct Unstake<'info> {
     uuid.as_bytes(),
     ],
     bump = mint_staked_bump,
-    space = MINT_STAKED_SIZE_START + 32 * (user_account.mint_staked_count + 1) as usize)]
+    space = MINT_STAKED_SIZE_START + 32 * (user_account.mint_staked_count + 1) as usize + STAKE_HISTORY_SIZE * user_account.stake_history_count as usize)]
     mint_staked: ProgramAccount<'info, MintStaked>,
 
     // existing mint staked account
@@ -880,9 +1814,10 @@ pub struct Unstake<'info> {
 }
 
 #[derive(Accounts)]
-pub struct ClaimReward<'info> {
+#[instruction(mint_staked_bump: u8, uuid: String, count: u32)]
+pub struct StakeMany<'info> {
     #[account(mut, signer)]
-    user: AccountInfo<'info>,
+    staker: AccountInfo<'info>,
 
     // Pool Account
     // verify pool is not paused
@@ -892,71 +1827,407 @@ pub struct ClaimReward<'info> {
     has_one = authority,
     constraint = pool_account.is_initialized == true,
     constraint = pool_account.paused == false,
+    constraint = pool_account.config == * config.to_account_info().key,
     )]
     pool_account: ProgramAccount<'info, Pool>,
 
+    // the config account
+    #[account(mut, has_one = authority)]
+    config: ProgramAccount<'info, Config>,
+
     // Pool owner
     authority: AccountInfo<'info>,
 
-    #[account(mut)]
-    reward_vault: Box<Account<'info, TokenAccount>>,
-
     // user account
     // verify owner is the signer
     // verify pool is the pool account
     #[account(
     mut,
     constraint = user_account.pool == * pool_account.to_account_info().key,
-    constraint = user_account.user == * user.key,
+    constraint = user_account.user == * staker.key,
+    constraint = user_account.mint_staked == * current_mint_staked.to_account_info().key,
     )]
     user_account: ProgramAccount<'info, User>,
 
-    // send reward to user reward vault
-    #[account(mut)]
-    reward_to_account: Box<Account<'info, TokenAccount>>,
+    // new mint staked account to store all the mint staked for the user
+    // remaining accounts: the `count` nft token accounts to stake, checked against the config in process
+    #[account(
+    init,
+    payer = staker,
+    seeds = [
+    PREFIX_MINT.as_bytes(),
+    pool_account.to_account_info().key.as_ref(),
+    user_account.to_account_info().key.as_ref(),
+    uuid.as_bytes(),
+    ],
+    bump = mint_staked_bump,
+    space = MINT_STAKED_SIZE_START + 32 * (user_account.mint_staked_count + count) as usize + STAKE_HISTORY_SIZE * (user_account.stake_history_count + count) as usize)]
+    mint_staked: ProgramAccount<'info, MintStaked>,
+
+    // existing mint staked account
+    #[account(mut,
+    constraint = current_mint_staked.pool == * pool_account.to_account_info().key,
+    constraint = current_mint_staked.user_account == * user_account.to_account_info().key,
+    close = staker,
+    )]
+    current_mint_staked: ProgramAccount<'info, MintStaked>,
+
+    // The rent sysvar
+    rent: Sysvar<'info, Rent>,
 
     // The Token Program
     #[account(address = spl_token::id())]
     token_program: AccountInfo<'info>,
+
+    // system program
+    system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CloseUser<'info> {
-    // user owner
+#[instruction(mint_staked_bump: u8, uuid: String, count: u32)]
+pub struct UnstakeMany<'info> {
     #[account(mut, signer)]
-    user: AccountInfo<'info>,
+    staker: AccountInfo<'info>,
 
     // Pool Account
+    // verify pool is not paused
+    // verify owner
+    // verify config
     #[account(mut,
+    has_one = authority,
     constraint = pool_account.is_initialized == true,
     constraint = pool_account.paused == false,
+    constraint = pool_account.config == * config.to_account_info().key,
     )]
     pool_account: ProgramAccount<'info, Pool>,
 
+    // the config account
+    #[account(mut, has_one = authority)]
+    config: ProgramAccount<'info, Config>,
+
+    // Pool owner
+    authority: AccountInfo<'info>,
+
     // user account
-    // user has to unstake everything and claim everything (check in process) before close account
+    // verify owner is the signer
+    // verify pool is the pool account
     #[account(
     mut,
     constraint = user_account.pool == * pool_account.to_account_info().key,
-    constraint = user_account.user == * user.key,
-    close = user,
+    constraint = user_account.user == * staker.key,
+    constraint = user_account.mint_staked == * current_mint_staked.to_account_info().key,
     )]
     user_account: ProgramAccount<'info, User>,
-}
 
-#[derive(Accounts)]
-pub struct ClosePool<'info> {
-    // authority
-    #[account(mut, signer)]
-    authority: AccountInfo<'info>,
+    // new mint staked account to store all the mint staked for the user
+    // remaining accounts: the `count` nft token accounts to unstake, checked against the mint staked in process
+    #[account(
+    init,
+    payer = staker,
+    seeds = [
+    PREFIX_MINT.as_bytes(),
+    pool_account.to_account_info().key.as_ref(),
+    user_account.to_account_info().key.as_ref(),
+    uuid.as_bytes(),
+    ],
+    bump = mint_staked_bump,
+    space = MINT_STAKED_SIZE_START + 32 * user_account.mint_staked_count.saturating_sub(count) as usize + STAKE_HISTORY_SIZE * user_account.stake_history_count as usize)]
+    mint_staked: ProgramAccount<'info, MintStaked>,
 
-    // Pool Account
+    // existing mint staked account
     #[account(mut,
-    close = refundee,
-    constraint = pool_account.is_initialized == true,
-    constraint = pool_account.paused == false,
-    constraint = pool_account.authority == * authority.key,
-    constraint = pool_account.reward_vault == * reward_vault.to_account_info().key,
+    constraint = current_mint_staked.pool == * pool_account.to_account_info().key,
+    constraint = current_mint_staked.user_account == * user_account.to_account_info().key,
+    close = staker,
+    )]
+    current_mint_staked: ProgramAccount<'info, MintStaked>,
+
+    // The rent sysvar
+    rent: Sysvar<'info, Rent>,
+
+    // The Token Program
+    #[account(address = spl_token::id())]
+    token_program: AccountInfo<'info>,
+
+    // system program
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReward<'info> {
+    #[account(mut, signer)]
+    user: AccountInfo<'info>,
+
+    // Pool Account
+    // verify pool is not paused
+    // verify owner
+    // verify config
+    // verify reward vault
+    #[account(mut,
+    has_one = authority,
+    constraint = pool_account.is_initialized == true,
+    constraint = pool_account.paused == false,
+    constraint = pool_account.reward_vault == * reward_vault.to_account_info().key,
+    )]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // Pool owner
+    authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    reward_vault: Box<Account<'info, TokenAccount>>,
+
+    // user account
+    // verify owner is the signer
+    // verify pool is the pool account
+    #[account(
+    mut,
+    constraint = user_account.pool == * pool_account.to_account_info().key,
+    constraint = user_account.user == * user.key,
+    )]
+    user_account: ProgramAccount<'info, User>,
+
+    // current mint staked account of the user, holds the stake times for the loyalty bonus
+    #[account(
+    constraint = user_account.mint_staked == * mint_staked.to_account_info().key,
+    )]
+    mint_staked: ProgramAccount<'info, MintStaked>,
+
+    // send reward to user reward vault
+    // verify it holds the reward mint
+    #[account(mut,
+    constraint = reward_to_account.mint == pool_account.reward_mint,
+    )]
+    reward_to_account: Box<Account<'info, TokenAccount>>,
+
+    // The Token Program
+    #[account(address = spl_token::id())]
+    token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ViewPendingRewards<'info> {
+    // Pool Account
+    #[account(constraint = pool_account.is_initialized == true)]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // user account
+    // verify pool is the pool account
+    #[account(constraint = user_account.pool == * pool_account.to_account_info().key)]
+    user_account: ProgramAccount<'info, User>,
+
+    // current mint staked account of the user
+    #[account(constraint = user_account.mint_staked == * mint_staked.to_account_info().key)]
+    mint_staked: ProgramAccount<'info, MintStaked>,
+}
+
+#[derive(Accounts)]
+pub struct SetClaimDelegate<'info> {
+    #[account(signer)]
+    user: AccountInfo<'info>,
+
+    // Pool Account
+    #[account(constraint = pool_account.is_initialized == true)]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // user account
+    // verify owner is the signer
+    // verify pool is the pool account
+    #[account(
+    mut,
+    constraint = user_account.pool == * pool_account.to_account_info().key,
+    constraint = user_account.user == * user.key,
+    )]
+    user_account: ProgramAccount<'info, User>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimFor<'info> {
+    // delegate registered by the user
+    #[account(signer)]
+    claimer: AccountInfo<'info>,
+
+    // Pool Account
+    // verify pool is not paused
+    // verify owner
+    // verify reward vault
+    #[account(mut,
+    has_one = authority,
+    constraint = pool_account.is_initialized == true,
+    constraint = pool_account.paused == false,
+    constraint = pool_account.reward_vault == * reward_vault.to_account_info().key,
+    )]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // Pool owner
+    authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    reward_vault: Box<Account<'info, TokenAccount>>,
+
+    // user account
+    // verify pool is the pool account
+    // verify the claimer is the delegate of the user, nobody can claim for a user without a delegate
+    #[account(
+    mut,
+    constraint = user_account.pool == * pool_account.to_account_info().key,
+    constraint = user_account.claim_delegate != Pubkey::default(),
+    constraint = user_account.claim_delegate == * claimer.key,
+    )]
+    user_account: ProgramAccount<'info, User>,
+
+    // current mint staked account of the user, holds the stake times for the loyalty bonus
+    #[account(
+    constraint = user_account.mint_staked == * mint_staked.to_account_info().key,
+    )]
+    mint_staked: ProgramAccount<'info, MintStaked>,
+
+    // send reward to a user token account or the recipient registered by the user
+    // verify it holds the reward mint
+    #[account(mut,
+    constraint = reward_to_account.mint == pool_account.reward_mint,
+    constraint = reward_to_account.owner == user_account.user || user_account.claim_recipient == * reward_to_account.to_account_info().key,
+    )]
+    reward_to_account: Box<Account<'info, TokenAccount>>,
+
+    // The Token Program
+    #[account(address = spl_token::id())]
+    token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewardToAssociated<'info> {
+    #[account(mut, signer)]
+    user: AccountInfo<'info>,
+
+    // Pool Account
+    // verify pool is not paused
+    // verify owner
+    // verify reward vault and reward mint
+    #[account(mut,
+    has_one = authority,
+    has_one = reward_mint,
+    constraint = pool_account.is_initialized == true,
+    constraint = pool_account.paused == false,
+    constraint = pool_account.reward_vault == * reward_vault.to_account_info().key,
+    )]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // Pool owner
+    authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    reward_vault: Box<Account<'info, TokenAccount>>,
+
+    // user account
+    // verify owner is the signer
+    // verify pool is the pool account
+    #[account(
+    mut,
+    constraint = user_account.pool == * pool_account.to_account_info().key,
+    constraint = user_account.user == * user.key,
+    )]
+    user_account: ProgramAccount<'info, User>,
+
+    // current mint staked account of the user, holds the stake times for the loyalty bonus
+    #[account(
+    constraint = user_account.mint_staked == * mint_staked.to_account_info().key,
+    )]
+    mint_staked: ProgramAccount<'info, MintStaked>,
+
+    // reward mint
+    reward_mint: AccountInfo<'info>,
+
+    // associated token account of the user for the reward mint, created if it doesn't exist yet
+    // verify the address in process
+    #[account(mut)]
+    reward_to_account: AccountInfo<'info>,
+
+    // The rent sysvar
+    rent: Sysvar<'info, Rent>,
+
+    // The Token Program
+    #[account(address = spl_token::id())]
+    token_program: AccountInfo<'info>,
+
+    // The Associated Token Program
+    #[account(address = anchor_spl::associated_token::ID)]
+    associated_token_program: AccountInfo<'info>,
+
+    // system program
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseUser<'info> {
+    // user owner
+    #[account(mut, signer)]
+    user: AccountInfo<'info>,
+
+    // Pool Account
+    #[account(mut,
+    constraint = pool_account.is_initialized == true,
+    constraint = pool_account.paused == false,
+    )]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // user account
+    // user has to unstake everything and claim everything (check in process) before close account
+    // remaining accounts (optional): [reward_vault, reward_to_account, token_program] to claim pending rewards
+    #[account(
+    mut,
+    constraint = user_account.pool == * pool_account.to_account_info().key,
+    constraint = user_account.user == * user.key,
+    close = user,
+    )]
+    user_account: ProgramAccount<'info, User>,
+
+    // current mint staked account, closed together with the user account
+    #[account(mut,
+    constraint = mint_staked.pool == * pool_account.to_account_info().key,
+    constraint = mint_staked.user_account == * user_account.to_account_info().key,
+    constraint = user_account.mint_staked == * mint_staked.to_account_info().key,
+    close = user,
+    )]
+    mint_staked: ProgramAccount<'info, MintStaked>,
+}
+
+#[derive(Accounts)]
+pub struct CloseMintStaked<'info> {
+    // user owner, receives the rent of the mint staked account
+    #[account(mut, signer)]
+    user: AccountInfo<'info>,
+
+    // Pool Account
+    #[account(constraint = pool_account.is_initialized == true)]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // user account of the user in the pool, may be closed already
+    // verify the address in process
+    user_account: AccountInfo<'info>,
+
+    // orphaned mint staked account
+    #[account(mut,
+    constraint = mint_staked.pool == * pool_account.to_account_info().key,
+    constraint = mint_staked.user_account == * user_account.key,
+    close = user,
+    )]
+    mint_staked: ProgramAccount<'info, MintStaked>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    // authority
+    #[account(mut, signer)]
+    authority: AccountInfo<'info>,
+
+    // Pool Account
+    #[account(mut,
+    close = refundee,
+    constraint = pool_account.is_initialized == true,
+    constraint = pool_account.paused == false,
+    constraint = pool_account.authority == * authority.key,
+    constraint = pool_account.reward_vault == * reward_vault.to_account_info().key,
     constraint = pool_account.reward_duration_end > 0,
     constraint = pool_account.reward_duration_end < clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
     constraint = pool_account.token_stake_count == 0,
@@ -986,6 +2257,132 @@ pub struct ClosePool<'info> {
 }
 
 
+#[derive(Accounts)]
+#[instruction(migration_bump: u8)]
+pub struct BeginMigratePool<'info> {
+    // pool authority, receives the rent of the old pool account
+    #[account(mut, signer)]
+    authority: AccountInfo<'info>,
+
+    // pool account on an older layout
+    // verify owner and authority in process
+    #[account(mut)]
+    pool_account: AccountInfo<'info>,
+
+    // holds the old pool data until migrate_pool
+    #[account(init,
+    payer = authority,
+    seeds = [PREFIX_MIGRATION.as_bytes(), pool_account.key.as_ref()],
+    bump = migration_bump,
+    space = MIGRATION_SIZE_START + pool_account.data_len())]
+    migration: ProgramAccount<'info, Migration>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(pool_bump: u8)]
+pub struct MigratePool<'info> {
+    #[account(mut, signer)]
+    authority: AccountInfo<'info>,
+
+    // pool account recreated at the same address
+    #[account(init,
+    seeds = [PREFIX.as_bytes(), authority.key.as_ref(), config.key().as_ref()],
+    bump = pool_bump,
+    payer = authority,
+    space = POOL_SIZE)]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    #[account(has_one = authority)]
+    config: ProgramAccount<'info, Config>,
+
+    #[account(mut,
+    constraint = migration.account == pool_account.key(),
+    close = authority,
+    )]
+    migration: ProgramAccount<'info, Migration>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(migration_bump: u8)]
+pub struct BeginMigrateUser<'info> {
+    // user owner, receives the rent of the old user account
+    #[account(mut, signer)]
+    user: AccountInfo<'info>,
+
+    // Pool Account, already migrated
+    #[account(constraint = pool_account.is_initialized == true)]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // user account on an older layout
+    // verify owner, user and pool in process
+    #[account(mut)]
+    user_account: AccountInfo<'info>,
+
+    // holds the old user data until migrate_user
+    #[account(init,
+    payer = user,
+    seeds = [PREFIX_MIGRATION.as_bytes(), user_account.key.as_ref()],
+    bump = migration_bump,
+    space = MIGRATION_SIZE_START + user_account.data_len())]
+    migration: ProgramAccount<'info, Migration>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(user_bump: u8, mint_staked_bump: u8, uuid: String)]
+pub struct MigrateUser<'info> {
+    #[account(mut, signer)]
+    user: AccountInfo<'info>,
+
+    // Pool Account
+    #[account(mut, constraint = pool_account.is_initialized == true)]
+    pool_account: ProgramAccount<'info, Pool>,
+
+    // user account recreated at the same address
+    #[account(init,
+    payer = user,
+    seeds = [
+    PREFIX_USER.as_bytes(),
+    pool_account.to_account_info().key.as_ref(),
+    user.key.as_ref(),
+    ],
+    bump = user_bump,
+    space = USER_SIZE)]
+    user_account: ProgramAccount<'info, User>,
+
+    #[account(mut,
+    constraint = migration.account == user_account.key(),
+    close = user,
+    )]
+    migration: ProgramAccount<'info, Migration>,
+
+    // mint staked account of the user on an older layout
+    // verify owner and address in process
+    #[account(mut)]
+    current_mint_staked: AccountInfo<'info>,
+
+    // new mint staked account, the old layout plus the version byte and an empty stake history
+    #[account(
+    init,
+    payer = user,
+    seeds = [
+    PREFIX_MINT.as_bytes(),
+    pool_account.to_account_info().key.as_ref(),
+    user_account.to_account_info().key.as_ref(),
+    uuid.as_bytes(),
+    ],
+    bump = mint_staked_bump,
+    space = current_mint_staked.data_len() + 1 + 4)]
+    mint_staked: ProgramAccount<'info, MintStaked>,
+
+    system_program: Program<'info, System>,
+}
+
 pub const POOL_SIZE: usize = 8 + // discriminator
     1 + // is_initialized
     32 + // authority
@@ -999,7 +2396,27 @@ pub const POOL_SIZE: usize = 8 + // discriminator
     8 + // reward_duration_end
     4 + // token_stake_count
     4 + // user_count
-    4 + 32 * 5; // funders
+    4 + 32 * 5 + // legacy_funders
+    8 + // paused_at
+    8 + // paused_duration
+    1 + // version
+    8 + // total_funded
+    8 + // total_claimed
+    8 + // total_owed_estimate
+    8 + // last_fund_time
+    4 + // peak_staked
+    8 + // loyalty_step
+    4 + // loyalty_step_bps
+    4 + // loyalty_max_bps
+    8 * MAX_COUNT_BONUS + // count_bonus
+    4 + // funder_count
+    8 + // total_donated
+    4 + // mint_group_bonus_bps
+    8 + // paused_after_end
+    8 + // total_funder_funded
+    16 + // reward_per_token_stored
+    16 + // bonus_per_token_stored
+    4 * 3; // reserved
 
 #[account]
 #[derive(Default)]
@@ -1028,8 +2445,79 @@ pub struct Pool {
     pub token_stake_count: u32,
     /// User created
     pub user_count: u32,
-    /// authorized funders
-    pub funders: [Pubkey; 5],
+    /// Funders authorized before funder accounts, not used to authorize anymore
+    pub legacy_funders: [Pubkey; 5],
+    /// Time the pool was last paused
+    pub paused_at: u64,
+    /// Total time spent paused, excluding the current pause
+    pub paused_duration: u64,
+    /// Layout version, new fields come after it and before the reserved space
+    pub version: u8,
+    /// Reward tokens funded over the life of the pool
+    pub total_funded: u64,
+    /// Reward tokens paid out to users
+    pub total_claimed: u64,
+    /// Rewards settled to users and not paid out yet, accrual since each user's last update is not included
+    pub total_owed_estimate: u64,
+    /// Time of the last fund
+    pub last_fund_time: u64,
+    /// Highest token_stake_count reached
+    pub peak_staked: u32,
+    /// Continuous staking time per loyalty step in seconds, 0 disables the loyalty bonus
+    pub loyalty_step: u64,
+    /// Earning weight added per loyalty step, in basis points
+    pub loyalty_step_bps: u32,
+    /// Cap of the earning weight added by the loyalty steps, in basis points
+    pub loyalty_max_bps: u32,
+    /// Bonus for staking several nfts, unused rules are zero
+    // MAX_COUNT_BONUS written out, the IDL parser of anchor 0.17 only reads literal array lengths
+    pub count_bonus: [CountBonus; 4],
+    /// Funders with a funder account
+    pub funder_count: u32,
+    /// Reward tokens donated, not included in total_funded
+    pub total_donated: u64,
+    /// Sum of the bonus of every mint group in the config, in basis points
+    pub mint_group_bonus_bps: u32,
+    /// Time spent paused after reward_duration_end, reset when the pool is funded
+    pub paused_after_end: u64,
+    /// Reward tokens funded by funders other than the authority, included in total_funded
+    pub total_funder_funded: u64,
+    /// Reward per token accrued up to last_update_time, the reward rate integrated over the unpaused time before the end
+    pub reward_per_token_stored: u128,
+    /// Bonus reserve per token accrued up to last_update_time, caps the bonuses paid over the same time
+    pub bonus_per_token_stored: u128,
+    /// Reserved for new fields without resizing the account
+    pub reserved: [u32; 3],
+}
+
+impl Pool {
+    /// Total time spent paused up to `now`, including the current pause
+    pub fn paused_duration_at(&self, now: u64) -> u64 {
+        if self.paused {
+            self.paused_duration.checked_add(now.saturating_sub(self.paused_at)).unwrap()
+        } else {
+            self.paused_duration
+        }
+    }
+
+    /// Reward duration end at `now`, a pause that started before it moves it out by the time paused so far
+    pub fn reward_duration_end_at(&self, now: u64) -> u64 {
+        if self.paused && self.reward_duration_end > self.paused_at {
+            self.reward_duration_end.checked_add(now.saturating_sub(self.paused_at)).unwrap()
+        } else {
+            self.reward_duration_end
+        }
+    }
+
+    /// Reward duration end on the unpaused clock at `now`: the end minus the time paused before it
+    pub fn unpaused_reward_duration_end(&self, now: u64) -> u64 {
+        let mut paused_after_end = self.paused_after_end;
+        if self.paused && self.reward_duration_end <= self.paused_at {
+            paused_after_end = paused_after_end.checked_add(now.saturating_sub(self.paused_at)).unwrap();
+        }
+        let paused_before_end = self.paused_duration_at(now).saturating_sub(paused_after_end);
+        self.reward_duration_end_at(now).saturating_sub(paused_before_end)
+    }
 }
 
 
@@ -1057,7 +2545,17 @@ pub const USER_SIZE: usize = 8 + // discriminator
     4 + // mint_staked_count
     4 + 6 + // uuid + u32 le
     32 +  // mint_staked
-    8; //last update time
+    8 + //last update time
+    8 + // paused_duration_checkpoint
+    8 + // reward_owed
+    32 + // claim_delegate
+    32 + // claim_recipient
+    1 + // version
+    4 + // stake_history_count
+    4 + // set_bonus_bps
+    16 + // reward_per_token_paid
+    16 + // bonus_per_token_paid
+    4 * 6; // reserved
 
 // 32 + 32 + 128 + 64 + 32
 #[account]
@@ -1079,12 +2577,39 @@ pub struct User {
     pub mint_staked: Pubkey,
     //last update time for stake/unstake
     pub last_update_time: u64,
+    /// Pool paused duration at the last update, paused time is excluded from accrual
+    pub paused_duration_checkpoint: u64,
+    /// Reward not paid out because the reward vault was short, paid on the next claim
+    pub reward_owed: u64,
+    /// Account allowed to claim on behalf of the user, nobody if default
+    pub claim_delegate: Pubkey,
+    /// Token account registered by the user to receive claims made on their behalf
+    pub claim_recipient: Pubkey,
+    /// Layout version, new fields come after it and before the reserved space
+    pub version: u8,
+    /// Number of entries in the stake history of the mint_staked account
+    pub stake_history_count: u32,
+    /// Count and mint group bonus on the base reward, in basis points, evaluated on stake and unstake
+    pub set_bonus_bps: u32,
+    /// Pool reward per token at the last update
+    pub reward_per_token_paid: u128,
+    /// Pool bonus reserve per token at the last update
+    pub bonus_per_token_paid: u128,
+    /// Reserved for new fields without resizing the account
+    pub reserved: [u32; 6],
 }
 
 pub const MINT_STAKED_SIZE_START: usize = 8 + // discriminator
     32 + // pool
     32 + // user_account
-    4; // u32 len for Vec<Pubkey>
+    4 + // u32 len for Vec<Pubkey>
+    1 + // version
+    4; // u32 len for Vec<StakeHistory>
+
+pub const STAKE_HISTORY_SIZE: usize = 32 + // mint
+    8 + // staked_at
+    8 + // staked_seconds
+    8; // paused_duration_checkpoint
 
 #[account]
 #[derive(Default)]
@@ -1095,18 +2620,112 @@ pub struct MintStaked {
     pub user_account: Pubkey,
     /// mint addresses
     pub mint_accounts: Vec<Pubkey>,  // theroctically account can hold (10,000,000 - 32 - 32)/32 = 312_497 mint addresses
+    /// Layout version, the account is recreated at its exact size on every stake change so it has no reserved space
+    pub version: u8,
+    /// One entry per nft mint ever staked by the user, kept after unstake
+    pub stake_history: Vec<StakeHistory>,
+}
+
+// scheduling of a fund
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FundMode {
+    /// Spread the amount and the rewards left to emit over a new reward duration from now
+    Reset,
+    /// Keep the reward rate and extend the reward duration end by the time the amount lasts at that rate
+    TopUp,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct CountBonus {
+    /// Staked nfts needed for the bonus
+    pub min_count: u32,
+    /// Bonus on the base reward, in basis points
+    pub bonus_bps: u32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Debug, PartialEq)]
+pub struct MintGroup {
+    /// Bonus on the base reward when all the mints are staked by one user, in basis points
+    pub bonus_bps: u32,
+    /// Config mints of the group
+    pub mints: Vec<Pubkey>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Debug, PartialEq)]
+pub struct StakeHistory {
+    /// nft mint
+    pub mint: Pubkey,
+    /// Time the nft was staked, 0 while it is not staked
+    pub staked_at: u64,
+    /// Seconds staked before the current stake
+    pub staked_seconds: u64,
+    /// Pool paused duration when the nft was staked, the loyalty age excludes paused time
+    pub paused_duration_checkpoint: u64,
+}
+
+impl StakeHistory {
+    /// Total seconds staked up to `now`, including the current stake
+    pub fn staked_seconds_at(&self, now: u64) -> u64 {
+        if self.staked_at == 0 {
+            self.staked_seconds
+        } else {
+            self.staked_seconds.checked_add(now.saturating_sub(self.staked_at)).unwrap()
+        }
+    }
+}
+
+pub const FUNDER_SIZE: usize = 8 + // discriminator
+    32 + // pool
+    32 + // funder
+    8 + // cap
+    8 + // funded
+    1; // authorized
+
+// funding allowance of a funder, seeds = [PREFIX_FUNDER, pool, funder]
+#[account]
+#[derive(Default)]
+pub struct Funder {
+    /// Pool the funder is authorized on
+    pub pool: Pubkey,
+    /// Funder pubkey
+    pub funder: Pubkey,
+    /// Cap of the total amount funded
+    pub cap: u64,
+    /// Reward tokens funded
+    pub funded: u64,
+    /// Cleared by deauthorize_funder, the account stays so the funder is still refunded on close_pool
+    pub authorized: bool,
+}
+
+pub const MIGRATION_SIZE_START: usize = 8 + // discriminator
+    32 + // account
+    4; // u32 len for Vec<u8>
+
+// old layout data of a pool or user account while it is recreated with the current layout
+#[account]
+#[derive(Default)]
+pub struct Migration {
+    /// Account being migrated
+    pub account: Pubkey,
+    /// Data of the account as it was, discriminator included
+    pub data: Vec<u8>,
 }
 
+// error codes are 300 + the variant index, deprecated variants are never returned
+// and stay in place so the codes after them don't move
 #[error]
 pub enum ErrorCode {
     #[msg("Insufficient tokens to stake.")]
     InsufficientTokenStake,
+    // deprecated
     #[msg("Insufficient funds to stake.")]
     InsufficientFundStake,
+    // deprecated
     #[msg("Insufficient funds to unstake.")]
     InsufficientFundUnstake,
     #[msg("Amount must be greater than zero.")]
     AmountMustBeGreaterThanZero,
+    // deprecated
     #[msg("Reward B cannot be funded - pool is single stake.")]
     SingleStakeTokenBCannotBeFunded,
     #[msg("Pool is paused or is not initialized.")]
@@ -1119,6 +2738,7 @@ pub enum ErrorCode {
     DurationTooShort,
     #[msg("Provided funder is already authorized to fund.")]
     FunderAlreadyAuthorized,
+    // deprecated
     #[msg("Maximum funders already authorized.")]
     MaxFunders,
     #[msg("Cannot deauthorize the primary pool authority.")]
@@ -1131,4 +2751,28 @@ pub enum ErrorCode {
     NumericalOverflowError,
     #[msg("Mint address is not stakable!")]
     InvalidMint,
+    #[msg("Pool is not paused.")]
+    PoolNotPaused,
+    #[msg("Invalid reward vault or reward destination account.")]
+    InvalidRewardAccount,
+    #[msg("User account does not belong to the signer.")]
+    InvalidUserAccount,
+    #[msg("Mint staked account is still in use.")]
+    MintStakedInUse,
+    #[msg("Account is already on the current layout.")]
+    AlreadyMigrated,
+    #[msg("Account does not match the migrated account.")]
+    InvalidMigration,
+    #[msg("Invalid bonus rule.")]
+    InvalidBonusRule,
+    #[msg("No room left for mint groups in the config account.")]
+    MintGroupsFull,
+    #[msg("Funding exceeds the funder cap.")]
+    FunderCapExceeded,
+    #[msg("Invalid funder account.")]
+    InvalidFunderAccount,
+    #[msg("Pool has no running reward period to top up.")]
+    TopUpWithoutRate,
+    #[msg("Token account passed more than once.")]
+    DuplicateTokenAccount,
 }
\ No newline at end of file
@@ -1,57 +1,586 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::{clock};
+use anchor_lang::solana_program::{clock, system_instruction};
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_spl::token::TokenAccount;
 use std::convert::TryInto;
-use crate::{Pool, User};
+use spl_token::instruction::AuthorityType::AccountOwner;
+use crate::{get_mint_groups, Config, ErrorCode, FundMode, Funder, MintGroup, Pool, StakeHistory, Unstake, User, PREFIX};
+use crate::constants::{ACCOUNT_VERSION, REWARD_PER_TOKEN_VERSION};
+use crate::events::{Claimed, Unstaked};
 
 const PRECISION: u128 = u64::MAX as u128;
+const BASIS_POINTS: u128 = 10_000;
 
-// update user pending reward, update user last update time
+// update the pool accumulators, update user pending reward, update user last update time
 #[inline(always)]
 pub fn update_rewards(
     pool: &mut ProgramAccount<Pool>,
     user: Option<&mut ProgramAccount<User>>,
+    stake_history: &[StakeHistory],
 ) -> ProgramResult {
     let clock = clock::Clock::get().unwrap();
 
     let now = clock.unix_timestamp.try_into().unwrap();
+    update_reward_per_token(pool, now);
 
     if let Some(u) = user {
-        // calculate time elapsed since last update
-        let time_diff = std::cmp::max(now - u.last_update_time, 0 as u64);
         // update user reward to pass it to pending reward
-        u.reward_earned_pending = earned(
-            time_diff,
-            u.mint_staked_count,
-            pool.reward_rate_per_token,
-            u.reward_earned_pending,
-        );
+        let (reward_earned_pending, paused_duration) = pending_rewards(pool, u, stake_history, now);
+        let accrued = reward_earned_pending.checked_sub(u.reward_earned_pending).unwrap();
+        pool.total_owed_estimate = pool.total_owed_estimate.checked_add(accrued).unwrap();
+        u.reward_earned_pending = reward_earned_pending;
         // update time in user account
         u.last_update_time = now;
+        u.paused_duration_checkpoint = paused_duration;
+        checkpoint_reward_per_token(pool, u);
     }
     Ok(())
 }
 
+// move the user accumulator checkpoints to the ones of the pool, the pool has to be updated to now before
+// the user is on the current layout from there
+pub fn checkpoint_reward_per_token(pool: &Pool, user: &mut User) {
+    user.reward_per_token_paid = pool.reward_per_token_stored;
+    user.bonus_per_token_paid = pool.bonus_per_token_stored;
+    user.version = ACCOUNT_VERSION;
+}
+
+// reward per token and bonus reserve per token of the pool at `now`
+// nothing accrues while paused or after the reward duration end, pause and resume update the accumulators
+pub fn reward_per_token_at(pool: &Pool, now: u64) -> (u128, u128) {
+    if pool.paused {
+        return (pool.reward_per_token_stored, pool.bonus_per_token_stored);
+    }
+    let elapsed = std::cmp::min(now, pool.reward_duration_end).saturating_sub(pool.last_update_time);
+    let reward = pool.reward_rate_per_token.checked_mul(elapsed as u128).unwrap();
+    (
+        pool.reward_per_token_stored.checked_add(reward).unwrap(),
+        pool.bonus_per_token_stored.checked_add(bps_share(reward, max_bonus_bps(pool))).unwrap(),
+    )
+}
+
+// accrue the pool accumulators up to `now`
+// has to run before any change of the reward rate, the reward duration end, the bonus rules or the paused state
+pub fn update_reward_per_token(pool: &mut Pool, now: u64) {
+    let (reward_per_token, bonus_per_token) = reward_per_token_at(pool, now);
+    pool.reward_per_token_stored = reward_per_token;
+    pool.bonus_per_token_stored = bonus_per_token;
+    pool.last_update_time = now;
+}
+
+// user pending reward at `now` without mutating the accounts, `stake_history` is the one of the user mint staked account
+// returns the new pending reward and the pool paused duration at `now`
 #[inline(always)]
-pub fn earned(
-    elapsed_time: u64,
-    balance_staked: u32,
-    reward_rate_per_token: u128,
-    user_reward_per_token_pending: u64,
-) -> u64 {
-    /*
-    earned reward = (now - last_update_time) * reward rate * balance_staked + user_rewards_x_pending
-    returns new pending rewards
-     */
-    let earned = (reward_rate_per_token as u128)
-        .checked_div(PRECISION)
+pub fn pending_rewards(pool: &Pool, user: &User, stake_history: &[StakeHistory], now: u64) -> (u64, u64) {
+    // interval since the last update, excluding the time the pool spent paused and the time after the reward period
+    // times are measured on the unpaused clock: time minus the pool paused duration at that time
+    let paused_duration = pool.paused_duration_at(now);
+    let from = user.last_update_time.saturating_sub(user.paused_duration_checkpoint);
+    let to = std::cmp::min(now.saturating_sub(paused_duration), pool.unpaused_reward_duration_end(now));
+    // nothing accrues for a user updated after the end
+    let to = std::cmp::max(to, from);
+    let time_diff = to.checked_sub(from).unwrap();
+
+    // reward per token and bonus reserve per token over the interval, at the rate of each part of it
+    // users from before the accumulators have no checkpoint, their interval is priced at the current rate once
+    let (reward_per_token, bonus_per_token) = if user.version < REWARD_PER_TOKEN_VERSION {
+        let reward_per_token = pool.reward_rate_per_token.checked_mul(time_diff as u128).unwrap();
+        (reward_per_token, bps_share(reward_per_token, max_bonus_bps(pool)))
+    } else {
+        let (reward_per_token_stored, bonus_per_token_stored) = reward_per_token_at(pool, now);
+        (
+            reward_per_token_stored.checked_sub(user.reward_per_token_paid).unwrap(),
+            bonus_per_token_stored.checked_sub(user.bonus_per_token_paid).unwrap(),
+        )
+    };
+    let base_earned = per_token_earned(reward_per_token, user.mint_staked_count);
+
+    // set bonus on the base reward of all staked nfts, rules removed since the last stake change don't pay
+    let set_bonus_bps = std::cmp::min(user.set_bonus_bps, max_set_bonus_bps(pool));
+    let set_bonus = bps_share(base_earned as u128, set_bonus_bps as u128);
+
+    // loyalty bonus of every staked nft over the same unpaused interval, ages are measured on the same clock
+    // the loyalty weight is paid at the average rate of the interval
+    let mut bonus: u128 = 0;
+    for entry in stake_history.iter().filter(|x| x.staked_at != 0) {
+        let staked_at = entry.staked_at.saturating_sub(entry.paused_duration_checkpoint);
+        let age_from = from.saturating_sub(staked_at);
+        let age_to = to.saturating_sub(staked_at);
+        bonus = bonus
+            .checked_add(loyalty_bonus(pool, age_to).checked_sub(loyalty_bonus(pool, age_from)).unwrap())
+            .unwrap();
+    }
+    let loyalty_earned = if time_diff == 0 {
+        0
+    } else {
+        reward_per_token
+            .checked_div(time_diff as u128)
+            .unwrap()
+            .checked_div(PRECISION)
+            .unwrap()
+            .checked_mul(bonus)
+            .unwrap()
+            .checked_div(BASIS_POINTS)
+            .unwrap()
+    };
+
+    // the bonuses never pay more than the reserve kept for them over the interval, also when the rules changed in it
+    let bonus_earned = std::cmp::min(
+        set_bonus.checked_add(loyalty_earned).unwrap(),
+        per_token_earned(bonus_per_token, user.mint_staked_count) as u128,
+    );
+    let reward_earned_pending = (user.reward_earned_pending as u128)
+        .checked_add(base_earned as u128)
         .unwrap()
-        .checked_mul(balance_staked as u128)
+        .checked_add(bonus_earned)
         .unwrap()
-        .checked_mul(elapsed_time as u128)
+        .try_into()
+        .unwrap();
+    (reward_earned_pending, paused_duration)
+}
+
+// loyalty weight of one nft integrated over its first `age` seconds of stake, in basis point seconds
+// the weight is `loyalty_step_bps` per full `loyalty_step`, capped at `loyalty_max_bps`
+pub fn loyalty_bonus(pool: &Pool, age: u64) -> u128 {
+    if pool.loyalty_step == 0 || pool.loyalty_step_bps == 0 {
+        return 0;
+    }
+    let step = pool.loyalty_step as u128;
+    let step_bps = pool.loyalty_step_bps as u128;
+    let max_bps = pool.loyalty_max_bps as u128;
+    let age = age as u128;
+
+    // first step at the cap, the weight stays at max_bps from there
+    let cap_steps = max_bps.checked_add(step_bps - 1).unwrap().checked_div(step_bps).unwrap();
+    let steps = std::cmp::min(age.checked_div(step).unwrap(), cap_steps);
+    // full steps 0..steps at weight j * step_bps, then the rest of the age at the current weight
+    let full_steps = step_bps
+        .checked_mul(step)
+        .unwrap()
+        .checked_mul(steps.checked_mul(steps.saturating_sub(1)).unwrap() / 2)
+        .unwrap();
+    let weight = std::cmp::min(steps.checked_mul(step_bps).unwrap(), max_bps);
+    full_steps
+        .checked_add(weight.checked_mul(age.checked_sub(steps.checked_mul(step).unwrap()).unwrap()).unwrap())
+        .unwrap()
+}
+
+// reward of `balance_staked` nfts for `reward_per_token`, the reward per token in PRECISION units
+#[inline(always)]
+pub fn per_token_earned(reward_per_token: u128, balance_staked: u32) -> u64 {
+    reward_per_token
+        .checked_mul(balance_staked as u128)
         .unwrap()
-        .checked_add(user_reward_per_token_pending as u128)
+        .checked_div(PRECISION)
         .unwrap()
         .try_into()
+        .unwrap()
+}
+
+// highest set bonus the rules pay: the best count rule plus every mint group
+pub fn max_set_bonus_bps(pool: &Pool) -> u32 {
+    let count_bonus = pool.count_bonus.iter().map(|x| x.bonus_bps).max().unwrap_or(0);
+    count_bonus.checked_add(pool.mint_group_bonus_bps).unwrap()
+}
+
+// highest bonus a staked nft can earn on top of its base reward, in basis points
+// fund keeps a reserve of this share so the bonuses are paid out of the funded amount
+pub fn max_bonus_bps(pool: &Pool) -> u128 {
+    let loyalty_bonus = if pool.loyalty_step == 0 || pool.loyalty_step_bps == 0 {
+        0
+    } else {
+        pool.loyalty_max_bps as u128
+    };
+    loyalty_bonus.checked_add(max_set_bonus_bps(pool) as u128).unwrap()
+}
+
+// `value` * `bps` / BASIS_POINTS split on the quotient and remainder so it can't overflow
+fn bps_share(value: u128, bps: u128) -> u128 {
+    let remainder = value.checked_rem(BASIS_POINTS).unwrap().checked_mul(bps).unwrap();
+    value
+        .checked_div(BASIS_POINTS)
+        .unwrap()
+        .checked_mul(bps)
+        .unwrap()
+        .checked_add(remainder.checked_div(BASIS_POINTS).unwrap())
+        .unwrap()
+}
+
+// base share of `amount`, the rest is the bonus reserve
+// amount * BASIS_POINTS / (BASIS_POINTS + max bonus) split on the quotient and remainder so it can't overflow
+fn base_amount(pool: &Pool, amount: u128) -> u128 {
+    let total_bps = BASIS_POINTS.checked_add(max_bonus_bps(pool)).unwrap();
+    let remainder = amount.checked_rem(total_bps).unwrap().checked_mul(BASIS_POINTS).unwrap();
+    amount
+        .checked_div(total_bps)
+        .unwrap()
+        .checked_mul(BASIS_POINTS)
+        .unwrap()
+        .checked_add(remainder.checked_div(total_bps).unwrap())
+        .unwrap()
+}
+
+// reward rate per token after funding `amount` at `now`
+// (base share of the new funded amount + remaining base amount in the pool) / total nft quantity / duration (seconds)
+pub fn funded_reward_rate(pool: &Pool, num_mint: u32, amount: u64, now: u64) -> u128 {
+    // remaining base reward in the pool = reward rate per token * remaining time * number of token, its reserve is kept with it
+    let leftover = if now >= pool.reward_duration_end {
+        0
+    } else {
+        let remaining = pool.reward_duration_end.checked_sub(now).unwrap();
+        pool.reward_rate_per_token
+            .checked_mul(remaining as u128)
+            .unwrap()
+            .checked_mul(num_mint as u128)
+            .unwrap()
+    };
+    base_amount(pool, (amount as u128).checked_mul(PRECISION).unwrap())
+        .checked_add(leftover)
+        .unwrap()
+        .checked_div(pool.reward_duration as u128)
+        .unwrap()
+        .checked_div(num_mint as u128)
+        .unwrap()
+}
+
+// reward rate per token once the bonus rules changed from `old_bonus_bps` to the ones of `pool`
+// the rate plus the highest bonus keeps paying out what was funded per nft second
+pub fn rescaled_reward_rate(pool: &Pool, old_bonus_bps: u128) -> u128 {
+    pool.reward_rate_per_token
+        .checked_div(BASIS_POINTS.checked_add(max_bonus_bps(pool)).unwrap())
+        .unwrap()
+        .checked_mul(BASIS_POINTS.checked_add(old_bonus_bps).unwrap())
+        .unwrap()
+}
+
+// reward duration end after topping up `amount` at `now` without changing the reward rate
+// the base share of the amount lasts base / (reward rate per token * total nft quantity) seconds, added to the current end
+// fails when there is no rate to keep: the pool was never funded or its reward period is over
+// and when the amount would push the end past u64, anyone can donate
+pub fn topped_up_duration_end(pool: &Pool, num_mint: u32, amount: u64, now: u64) -> core::result::Result<u64, ProgramError> {
+    let reward_duration_end = pool.reward_duration_end_at(now);
+    if pool.reward_rate_per_token == 0 || now >= reward_duration_end {
+        return Err(ErrorCode::TopUpWithoutRate.into());
+    }
+    let per_second = pool.reward_rate_per_token.checked_mul(num_mint as u128).ok_or(ErrorCode::NumericalOverflowError)?;
+    let base = base_amount(pool, (amount as u128).checked_mul(PRECISION).unwrap());
+    let extension: u64 = base.checked_div(per_second).unwrap().try_into().map_err(|_| ErrorCode::NumericalOverflowError)?;
+    pool.reward_duration_end.checked_add(extension).ok_or_else(|| ErrorCode::NumericalOverflowError.into())
+}
+
+// reward rate per token and reward duration end after funding `amount` in `mode` at `now`
+pub fn fund_schedule(pool: &Pool, num_mint: u32, amount: u64, mode: FundMode, now: u64) -> core::result::Result<(u128, u64), ProgramError> {
+    if amount == 0 {
+        return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+    }
+    match mode {
+        FundMode::Reset => Ok((
+            funded_reward_rate(pool, num_mint, amount, now),
+            now.checked_add(pool.reward_duration).ok_or(ErrorCode::NumericalOverflowError)?,
+        )),
+        FundMode::TopUp => Ok((pool.reward_rate_per_token, topped_up_duration_end(pool, num_mint, amount, now)?)),
+    }
+}
+
+// settle the pending reward against the vault balance, returns the amount to pay out
+// the unpaid remainder stays owed to the user
+pub fn settle_rewards(user: &mut User, vault_balance: u64) -> u64 {
+    // amount due = pending reward + reward owed from previous partial payouts
+    let reward_due = user.reward_earned_pending.checked_add(user.reward_owed).unwrap();
+    let reward_amount = std::cmp::min(reward_due, vault_balance);
+
+    user.reward_earned_pending = 0;
+    user.reward_owed = reward_due.checked_sub(reward_amount).unwrap();
+    user.reward_earned_claimed = user.reward_earned_claimed.checked_add(reward_amount).unwrap();
+    reward_amount
+}
+
+// set bonus in basis points: the best count rule reached plus every mint group with all its mints staked
+// staked mints come from the stake history, nfts staked before it was recorded don't complete a group
+pub fn set_bonus_bps(pool: &Pool, mint_groups: &[MintGroup], mint_staked_count: u32, stake_history: &[StakeHistory]) -> u32 {
+    let count_bonus = pool.count_bonus
+        .iter()
+        .filter(|x| x.min_count > 0 && x.min_count <= mint_staked_count)
+        .map(|x| x.bonus_bps)
+        .max()
+        .unwrap_or(0);
+    let is_staked = |mint: &Pubkey| stake_history.iter().any(|x| x.mint == *mint && x.staked_at != 0);
+    mint_groups
+        .iter()
+        .filter(|x| x.mints.iter().all(is_staked))
+        .fold(count_bonus, |total, x| total.checked_add(x.bonus_bps).unwrap())
+}
+
+// re-evaluate the set bonus of the user once its staked nfts changed, rewards have to be settled before
+pub fn update_set_bonus(pool: &Pool, config: &ProgramAccount<Config>, user: &mut User, stake_history: &[StakeHistory]) -> ProgramResult {
+    let mint_groups = get_mint_groups(&config.to_account_info().data.borrow(), config.num_mint)?;
+    user.set_bonus_bps = set_bonus_bps(pool, &mint_groups, user.mint_staked_count, stake_history);
+    Ok(())
+}
+
+// start the stake clock of `mint`, the entry is added on the first stake of the mint by the user
+// `paused_duration` is the pool paused duration at `now`, the loyalty age starts from it
+pub fn record_stake(history: &mut Vec<StakeHistory>, mint: Pubkey, now: u64, paused_duration: u64) {
+    match history.iter_mut().find(|x| x.mint == mint) {
+        Some(entry) => {
+            entry.staked_at = now;
+            entry.paused_duration_checkpoint = paused_duration;
+        }
+        None => history.push(StakeHistory {
+            mint,
+            staked_at: now,
+            staked_seconds: 0,
+            paused_duration_checkpoint: paused_duration,
+        }),
+    }
+}
+
+// stop the stake clock of `mint` and add the stake to its staked seconds
+// nfts staked before the history was recorded have no entry
+pub fn record_unstake(history: &mut [StakeHistory], mint: &Pubkey, now: u64) {
+    if let Some(entry) = history.iter_mut().find(|x| x.mint == *mint && x.staked_at != 0) {
+        entry.staked_seconds = entry.staked_seconds_at(now);
+        entry.staked_at = 0;
+    }
+}
+
+// create the program account `account` at a pda signed by `seeds`, `payer` tops it up to rent exemption
+// create_account fails on an address that already holds lamports, anyone can send some to a pda before it is created
+pub fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    owner: &Pubkey,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let required = Rent::get()?.minimum_balance(space).saturating_sub(account.lamports());
+    if required > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, required),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, owner),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+
+// read account data written with an older layout of `T`, including the discriminator
+// layouts only ever append fields, the missing bytes are zero filled so the version reads as 0
+pub fn read_older_layout<T: AccountDeserialize>(data: &[u8], size: usize) -> Result<T, ProgramError> {
+    if data.len() >= size {
+        return Err(ErrorCode::AlreadyMigrated.into());
+    }
+    let mut padded = data.to_vec();
+    padded.resize(size, 0);
+    T::try_deserialize(&mut &padded[..])
+}
+
+// close an account owned by the program without deserializing it, the lamports go to `destination`
+pub fn close_account_info(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    **destination.lamports.borrow_mut() = destination.lamports().checked_add(lamports).unwrap();
+    **account.lamports.borrow_mut() = 0;
+    for byte in account.try_borrow_mut_data()?.iter_mut() {
+        *byte = 0;
+    }
+    Ok(())
+}
+
+// settle the user pending and owed reward and transfer it from the reward vault, capped by the vault balance
+pub fn transfer_pending_rewards<'info>(
+    pool: &mut ProgramAccount<'info, Pool>,
+    user: &mut ProgramAccount<'info, User>,
+    reward_vault: &Account<'info, TokenAccount>,
+    reward_to_account: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    if user.reward_earned_pending == 0 && user.reward_owed == 0 {
+        return Ok(());
+    }
+
+    let (_pool_pda, pool_bump) = Pubkey::find_program_address(&[PREFIX.as_bytes(),
+        pool.authority.as_ref(),
+        pool.config.as_ref(),
+    ], program_id);
+    let seeds = &[PREFIX.as_bytes(),
+        pool.authority.as_ref(),
+        pool.config.as_ref(),
+        &[pool_bump]]; // need this to sign the pda, match the authority
+
+    let reward_amount = settle_rewards(user, reward_vault.amount);
+
+    let reward_to_key = reward_to_account.key();
+    if reward_amount > 0 {
+        let token_accounts = anchor_spl::token::Transfer {
+            from: reward_vault.to_account_info(),
+            to: reward_to_account,
+            authority: pool.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(token_program, token_accounts);
+        msg!("Calling the token program to transfer reward {} to the user", reward_amount);
+        anchor_spl::token::transfer(
+            cpi_ctx.with_signer(&[&seeds[..]]),
+            reward_amount,
+        )?;
+    }
+
+    pool.total_claimed = pool.total_claimed.checked_add(reward_amount).unwrap();
+    // users migrated from an older layout carry rewards that were never added to the estimate
+    pool.total_owed_estimate = pool.total_owed_estimate.saturating_sub(reward_amount);
+
+    emit!(Claimed {
+        pool: pool.key(),
+        user: user.user,
+        reward_to_account: reward_to_key,
+        amount: reward_amount,
+        reward_owed: user.reward_owed,
+        reward_earned_claimed: user.reward_earned_claimed,
+        timestamp: clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+    });
+    Ok(())
+}
+
+// reward vault and reward destination token accounts
+pub type RewardAccounts<'info> = (Account<'info, TokenAccount>, Account<'info, TokenAccount>);
+
+// optional reward accounts passed as remaining accounts: [reward_vault, reward_to_account, ..]
+// verify the vault is the pool reward vault and the destination holds the reward mint
+pub fn optional_reward_accounts<'info>(
+    pool: &Pool,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> core::result::Result<Option<RewardAccounts<'info>>, ProgramError> {
+    if remaining_accounts.len() < 2 {
+        return Ok(None);
+    }
+    let reward_vault: Account<TokenAccount> = Account::try_from(&remaining_accounts[0])?;
+    let reward_to_account: Account<TokenAccount> = Account::try_from(&remaining_accounts[1])?;
+    if reward_vault.key() != pool.reward_vault || reward_to_account.mint != pool.reward_mint {
+        return Err(ErrorCode::InvalidRewardAccount.into());
+    }
+    Ok(Some((reward_vault, reward_to_account)))
+}
+
+// true when the same account is passed more than once, checked before any bookkeeping on the batch
+pub fn has_duplicate_accounts(accounts: &[AccountInfo]) -> bool {
+    let mut keys: Vec<&Pubkey> = accounts.iter().map(|x| x.key).collect();
+    keys.sort();
+    keys.dedup();
+    keys.len() != accounts.len()
+}
+
+// move the unstaked nft out of the mint staked account and give the token authority back to the staker
+// shared by unstake and emergency_unstake once the rewards are settled or forfeited
+pub fn release_staked_nft(accounts: &mut Unstake, program_id: &Pubkey, uuid: String, now: u64, emergency: bool) -> ProgramResult {
+    accounts.user_account.mint_staked = *accounts.mint_staked.to_account_info().key;
+    accounts.user_account.mint_staked_count = accounts.user_account.mint_staked_count.checked_sub(1).unwrap();
+    accounts.user_account.uuid = uuid;
+
+    // count of user_account.mint_staked must be >= 1
+    let mint_staked = &mut accounts.mint_staked;
+    mint_staked.pool = *accounts.pool_account.to_account_info().key;
+    mint_staked.user_account = *accounts.user_account.to_account_info().key;
+    mint_staked.version = ACCOUNT_VERSION;
+
+    let current_mint_staked = &mut accounts.current_mint_staked;
+    for mint_address in &current_mint_staked.mint_accounts {
+        if mint_address != accounts.unstake_from_account.to_account_info().key {
+            mint_staked.mint_accounts.push(*mint_address);
+        }
+    }
+    mint_staked.stake_history = current_mint_staked.stake_history.clone();
+    record_unstake(&mut mint_staked.stake_history, &accounts.unstake_from_account.mint, now);
+    // an emergency unstake doesn't read the mint groups so it can't fail on them
+    // the user keeps only the count bonus until the next stake or unstake
+    if emergency {
+        let user = &mut accounts.user_account;
+        user.set_bonus_bps = set_bonus_bps(&accounts.pool_account, &[], user.mint_staked_count, &accounts.mint_staked.stake_history);
+    } else {
+        update_set_bonus(&accounts.pool_account, &accounts.config, &mut accounts.user_account, &accounts.mint_staked.stake_history)?;
+    }
+
+    // Transfer token authority
+    {
+        let (_pool_pda, pool_bump) = Pubkey::find_program_address(&[PREFIX.as_bytes(),
+            accounts.pool_account.authority.as_ref(),
+            accounts.pool_account.config.as_ref(),
+        ], program_id);
+        let seeds = &[PREFIX.as_bytes(),
+            accounts.pool_account.authority.as_ref(),
+            accounts.pool_account.config.as_ref(),
+            &[pool_bump]]; // need this to sign the pda, match the authority
+
+        let cpi_ctx = CpiContext::new(
+            accounts.token_program.to_account_info().clone(),
+            anchor_spl::token::SetAuthority {
+                current_authority: accounts.pool_account.to_account_info().clone(),
+                account_or_mint: accounts.unstake_from_account.to_account_info().clone(),
+            },
+        );
+        msg!("Calling the token program to transfer authority from pool to unstaker");
+        anchor_spl::token::set_authority(cpi_ctx.with_signer(&[&seeds[..]]),
+                                         AccountOwner,
+                                         Some(accounts.staker.key()))?;
+    }
+
+    emit!(Unstaked {
+        pool: accounts.pool_account.key(),
+        user: accounts.staker.key(),
+        count: 1,
+        mint_staked_count: accounts.user_account.mint_staked_count,
+        token_stake_count: accounts.pool_account.token_stake_count,
+        emergency,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+// funder account of the pool, owned by the program
+// share of `vault_amount` refunded to a funder that funded `funded` on close_pool
+// funders get back their part of everything put into the vault, funded or donated, split in proportion to what each funded
+pub fn funder_refund(pool: &Pool, vault_amount: u64, funded: u64) -> u64 {
+    let total = (pool.total_funded as u128).checked_add(pool.total_donated as u128).unwrap();
+    // nothing is tracked on pools funded before the totals
+    if total == 0 || pool.total_funder_funded == 0 {
+        return 0;
+    }
+    let funders_amount = (vault_amount as u128)
+        .checked_mul(pool.total_funder_funded as u128)
+        .unwrap()
+        .checked_div(total)
         .unwrap();
-    return earned;
-}
\ No newline at end of file
+    funders_amount
+        .checked_mul(funded as u128)
+        .unwrap()
+        .checked_div(pool.total_funder_funded as u128)
+        .unwrap()
+        .try_into()
+        .unwrap()
+}
+
+pub fn load_funder(program_id: &Pubkey, info: &AccountInfo, pool: &Pubkey) -> core::result::Result<Funder, ProgramError> {
+    if info.owner != program_id || info.data_is_empty() {
+        return Err(ErrorCode::InvalidFunderAccount.into());
+    }
+    let funder = Funder::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    if funder.pool != *pool {
+        return Err(ErrorCode::InvalidFunderAccount.into());
+    }
+    Ok(funder)
+}
+
+pub fn store_funder(info: &AccountInfo, funder: &Funder) -> ProgramResult {
+    let mut data = info.try_borrow_mut_data()?;
+    let mut cursor: &mut [u8] = &mut data;
+    funder.try_serialize(&mut cursor)
+}
@@ -0,0 +1,495 @@
+// property tests for the reward math: random fund / stake / unstake / claim sequences
+// run against a model of the pool using the same helpers as the program
+// the entitlements of all users are checked against the total funding after every action, over several reward periods
+
+use anchor_lang::prelude::Pubkey;
+use nft_staking::constants::ACCOUNT_VERSION;
+use nft_staking::utils::{
+    checkpoint_reward_per_token, funded_reward_rate, loyalty_bonus, max_bonus_bps, pending_rewards, record_stake, record_unstake,
+    rescaled_reward_rate, set_bonus_bps, settle_rewards, topped_up_duration_end, update_reward_per_token,
+};
+use nft_staking::{CountBonus, ErrorCode, MintGroup, Pool, StakeHistory, User};
+use proptest::prelude::*;
+
+const PRECISION: u128 = u64::MAX as u128;
+const START_TIME: u64 = 1_600_000_000;
+const MAX_FUND: u64 = 1_000_000_000_000;
+
+#[derive(Clone, Debug)]
+enum Action {
+    Fund(u64),
+    Stake(usize),
+    Unstake(usize),
+    Claim(usize),
+    Advance(u64),
+    // loyalty step, step bps, max bps
+    Ramp(u64, u32, u32),
+    // min count, bonus bps
+    CountBonus(u32, u32),
+    // group of every nft of a user, bonus bps
+    MintGroup(usize, u32),
+    ClearMintGroups,
+    Pause,
+    Resume,
+}
+
+struct Model {
+    pool: Pool,
+    users: Vec<User>,
+    // nfts held by each user, staked or not
+    nfts: Vec<u32>,
+    // stake history of each user, the mints of user i are mint(i, 0..nfts[i])
+    history: Vec<Vec<StakeHistory>>,
+    mint_groups: Vec<MintGroup>,
+    num_mint: u32,
+    now: u64,
+    vault: u64,
+    funded: u64,
+    paid: u64,
+    // exact reward of each user in PRECISION units, without the truncation of earned
+    exact: Vec<u128>,
+    // staked nft seconds of each user, bounds the truncation of earned
+    staked_seconds: Vec<u128>,
+}
+
+impl Model {
+    fn new(num_mint: u32, reward_duration: u64, users: usize) -> Self {
+        let pool = Pool {
+            is_initialized: true,
+            reward_duration,
+            reward_rate_per_token: 1,
+            last_update_time: START_TIME,
+            ..Pool::default()
+        };
+        let user = User {
+            last_update_time: START_TIME,
+            version: ACCOUNT_VERSION,
+            ..User::default()
+        };
+        // nft i belongs to user i % users
+        let nfts = (0..users as u32)
+            .map(|i| num_mint / users as u32 + u32::from(i < num_mint % users as u32))
+            .collect();
+        Model {
+            pool,
+            users: vec![user; users],
+            nfts,
+            history: vec![Vec::new(); users],
+            mint_groups: Vec::new(),
+            num_mint,
+            now: START_TIME,
+            vault: 0,
+            funded: 0,
+            paid: 0,
+            exact: vec![0; users],
+            staked_seconds: vec![0; users],
+        }
+    }
+
+    // same as utils::update_rewards
+    fn update_rewards(&mut self, i: usize) {
+        update_reward_per_token(&mut self.pool, self.now);
+        let user = &mut self.users[i];
+        // rewards stop at the reward duration end
+        let end = self.pool.reward_duration_end;
+        let elapsed = self.now.min(end).saturating_sub(user.last_update_time.min(end));
+        self.exact[i] += self.pool.reward_rate_per_token * (user.mint_staked_count as u128) * (elapsed as u128);
+        self.staked_seconds[i] += (user.mint_staked_count as u128) * (elapsed as u128);
+
+        let (reward_earned_pending, paused_duration) = pending_rewards(&self.pool, user, &self.history[i], self.now);
+        user.reward_earned_pending = reward_earned_pending;
+        user.last_update_time = self.now;
+        user.paused_duration_checkpoint = paused_duration;
+        checkpoint_reward_per_token(&self.pool, user);
+    }
+
+    fn apply(&mut self, action: &Action) {
+        match *action {
+            // fund, stake and unstake are rejected while the pool is paused
+            Action::Fund(_) | Action::Stake(_) | Action::Unstake(_) if self.pool.paused => {}
+            Action::Fund(amount) => {
+                let reward_rate_per_token = funded_reward_rate(&self.pool, self.num_mint, amount, self.now);
+                update_reward_per_token(&mut self.pool, self.now);
+                self.pool.reward_rate_per_token = reward_rate_per_token;
+                self.pool.reward_duration_end = self.now + self.pool.reward_duration;
+                self.pool.paused_after_end = 0;
+                self.vault += amount;
+                self.funded += amount;
+            }
+            Action::Stake(i) => {
+                let i = i % self.users.len();
+                if self.users[i].mint_staked_count < self.nfts[i] {
+                    self.update_rewards(i);
+                    let j = (0..self.nfts[i]).find(|&j| !self.is_staked(i, j)).unwrap();
+                    record_stake(&mut self.history[i], mint(i, j), self.now, self.pool.paused_duration_at(self.now));
+                    self.users[i].mint_staked_count += 1;
+                    self.update_set_bonus(i);
+                    self.pool.token_stake_count += 1;
+                }
+            }
+            Action::Unstake(i) => {
+                let i = i % self.users.len();
+                if self.users[i].mint_staked_count > 0 {
+                    self.update_rewards(i);
+                    let j = (0..self.nfts[i]).find(|&j| self.is_staked(i, j)).unwrap();
+                    record_unstake(&mut self.history[i], &mint(i, j), self.now);
+                    self.users[i].mint_staked_count -= 1;
+                    self.update_set_bonus(i);
+                    self.pool.token_stake_count -= 1;
+                }
+            }
+            Action::Claim(i) => {
+                let i = i % self.users.len();
+                self.update_rewards(i);
+                let amount = settle_rewards(&mut self.users[i], self.vault);
+                self.vault -= amount;
+                self.paid += amount;
+            }
+            Action::Advance(seconds) => self.now += seconds,
+            // same as set_loyalty_ramp
+            Action::Ramp(step, step_bps, max_bps) => {
+                update_reward_per_token(&mut self.pool, self.now);
+                let old_bonus_bps = max_bonus_bps(&self.pool);
+                self.pool.loyalty_step = step;
+                self.pool.loyalty_step_bps = step_bps;
+                self.pool.loyalty_max_bps = max_bps;
+                self.pool.reward_rate_per_token = rescaled_reward_rate(&self.pool, old_bonus_bps);
+            }
+            // same as set_count_bonus with a single rule
+            Action::CountBonus(min_count, bonus_bps) => {
+                update_reward_per_token(&mut self.pool, self.now);
+                let old_bonus_bps = max_bonus_bps(&self.pool);
+                self.pool.count_bonus = Default::default();
+                self.pool.count_bonus[0] = CountBonus { min_count, bonus_bps };
+                self.pool.reward_rate_per_token = rescaled_reward_rate(&self.pool, old_bonus_bps);
+            }
+            // same as add_mint_group
+            Action::MintGroup(i, bonus_bps) => {
+                let i = i % self.users.len();
+                update_reward_per_token(&mut self.pool, self.now);
+                let old_bonus_bps = max_bonus_bps(&self.pool);
+                let mints = (0..self.nfts[i]).map(|j| mint(i, j)).collect();
+                self.mint_groups.push(MintGroup { bonus_bps, mints });
+                self.pool.mint_group_bonus_bps += bonus_bps;
+                self.pool.reward_rate_per_token = rescaled_reward_rate(&self.pool, old_bonus_bps);
+            }
+            // same as pause and resume
+            Action::Pause => {
+                if !self.pool.paused {
+                    update_reward_per_token(&mut self.pool, self.now);
+                    self.pool.paused = true;
+                    self.pool.paused_at = self.now;
+                }
+            }
+            Action::Resume => {
+                if self.pool.paused {
+                    update_reward_per_token(&mut self.pool, self.now);
+                    let paused_interval = self.now - self.pool.paused_at;
+                    self.pool.paused_duration += paused_interval;
+                    if self.pool.reward_duration_end > self.pool.paused_at {
+                        self.pool.reward_duration_end += paused_interval;
+                    } else {
+                        self.pool.paused_after_end += paused_interval;
+                    }
+                    self.pool.paused = false;
+                }
+            }
+            // same as clear_mint_groups
+            Action::ClearMintGroups => {
+                update_reward_per_token(&mut self.pool, self.now);
+                let old_bonus_bps = max_bonus_bps(&self.pool);
+                self.mint_groups.clear();
+                self.pool.mint_group_bonus_bps = 0;
+                self.pool.reward_rate_per_token = rescaled_reward_rate(&self.pool, old_bonus_bps);
+            }
+        }
+    }
+
+    // same as utils::update_set_bonus
+    fn update_set_bonus(&mut self, i: usize) {
+        let user = &mut self.users[i];
+        user.set_bonus_bps = set_bonus_bps(&self.pool, &self.mint_groups, user.mint_staked_count, &self.history[i]);
+    }
+
+    // run `actions` then every user claims, the accounts are checked after each step
+    fn run(&mut self, actions: &[Action]) {
+        for action in actions {
+            self.apply(action);
+            self.check_accounts();
+        }
+        for i in 0..self.users.len() {
+            self.apply(&Action::Claim(i));
+        }
+        self.check_accounts();
+    }
+
+    fn is_staked(&self, i: usize, j: u32) -> bool {
+        self.history[i].iter().any(|x| x.mint == mint(i, j) && x.staked_at != 0)
+    }
+
+    // claimed + owed + pending
+    fn entitled(&self, i: usize) -> u64 {
+        let user = &self.users[i];
+        user.reward_earned_claimed + user.reward_owed + user.reward_earned_pending
+    }
+
+    fn check_accounts(&self) {
+        assert_eq!(self.vault, self.funded - self.paid);
+        let claimed: u64 = self.users.iter().map(|x| x.reward_earned_claimed).sum();
+        assert_eq!(claimed, self.paid);
+        // every fund, also one made while stake time is unsettled, only pays the time from the fund on
+        let entitled: u64 = (0..self.users.len()).map(|i| self.entitled(i)).sum();
+        assert!(entitled <= self.funded);
+    }
+}
+
+fn mint(i: usize, j: u32) -> Pubkey {
+    let mut bytes = [0; 32];
+    bytes[0] = i as u8;
+    bytes[1..5].copy_from_slice(&j.to_le_bytes());
+    Pubkey::new_from_array(bytes)
+}
+
+fn ramp() -> impl Strategy<Value = Action> {
+    (0..=100_000u64, 0..=5_000u32, 0..=20_000u32).prop_map(|(step, step_bps, max_bps)| Action::Ramp(step, step_bps, max_bps))
+}
+
+fn set_rule(users: usize) -> impl Strategy<Value = Action> {
+    prop_oneof![
+        (1..=8u32, 1..=10_000u32).prop_map(|(min_count, bonus_bps)| Action::CountBonus(min_count, bonus_bps)),
+        (0..users, 1..=10_000u32).prop_map(|(i, bonus_bps)| Action::MintGroup(i, bonus_bps)),
+        Just(Action::ClearMintGroups),
+    ]
+}
+
+fn action(users: usize, max_advance: u64) -> impl Strategy<Value = Action> {
+    prop_oneof![
+        (1..=MAX_FUND).prop_map(Action::Fund),
+        (0..users).prop_map(Action::Stake),
+        (0..users).prop_map(Action::Unstake),
+        (0..users).prop_map(Action::Claim),
+        (0..=max_advance).prop_map(Action::Advance),
+    ]
+}
+
+fn scenario() -> impl Strategy<Value = (u32, u64, usize, Vec<Action>)> {
+    // no pool changes
+    scenario_with(|_| Just(Action::Advance(0)))
+}
+
+// actions over several reward periods mixed with the `changes` of the pool, made by the authority
+fn scenario_with<S: Strategy<Value = Action> + 'static>(
+    changes: impl Fn(usize) -> S + Clone + 'static,
+) -> impl Strategy<Value = (u32, u64, usize, Vec<Action>)> {
+    (1..=8u32, 1..=1_000_000u64, 1..=4usize).prop_flat_map(move |(num_mint, duration, users)| {
+        let step = prop_oneof![8 => action(users, duration / 4 + 1), 1 => changes(users)];
+        let actions = prop::collection::vec(step, 1..64);
+        (Just(num_mint), Just(duration), Just(users), actions)
+    })
+}
+
+proptest! {
+    #[test]
+    fn claims_never_exceed_funding((num_mint, duration, users, actions) in scenario()) {
+        let mut model = Model::new(num_mint, duration, users);
+        let mut entitled = vec![0; users];
+        for action in &actions {
+            model.apply(action);
+            model.check_accounts();
+            // an unpaid claim stays owed, a user never loses earned rewards
+            for (i, before) in entitled.iter_mut().enumerate() {
+                let now = model.entitled(i);
+                prop_assert!(now >= *before);
+                *before = now;
+            }
+        }
+
+        for i in 0..users {
+            model.apply(&Action::Claim(i));
+        }
+        model.check_accounts();
+    }
+
+    #[test]
+    fn single_period_conserves_rewards(
+        (num_mint, duration, users, actions) in scenario(),
+        amount in 1..=1_000_000_000_000u64,
+    ) {
+        let mut model = Model::new(num_mint, duration, users);
+        model.apply(&Action::Fund(amount));
+        // time runs past the end of the period, nothing accrues there
+        for action in &actions {
+            let action = match *action {
+                Action::Fund(_) => continue,
+                ref action => action.clone(),
+            };
+            model.apply(&action);
+            model.check_accounts();
+        }
+
+        for i in 0..users {
+            model.apply(&Action::Claim(i));
+        }
+        model.check_accounts();
+
+        let mut total_exact = 0;
+        for i in 0..users {
+            // every update truncates the reward to whole tokens
+            let entitled = model.entitled(i) as u128;
+            let exact = model.exact[i] / PRECISION;
+            prop_assert!(entitled <= exact);
+            prop_assert!(exact - entitled <= model.staked_seconds[i]);
+            total_exact += model.exact[i];
+        }
+        prop_assert!(total_exact / PRECISION <= amount as u128);
+        let total_entitled: u64 = (0..users).map(|i| model.entitled(i)).sum();
+        prop_assert!(total_entitled <= amount);
+    }
+
+    #[test]
+    fn loyalty_ramp_conserves_rewards(
+        (num_mint, duration, users, actions) in scenario_with(|_| ramp()),
+        initial_ramp in ramp(),
+    ) {
+        let mut model = Model::new(num_mint, duration, users);
+        model.apply(&initial_ramp);
+        // base reward and loyalty bonus together stay within the funding, also when the ramp changes mid period
+        model.run(&actions);
+    }
+
+    #[test]
+    fn set_bonus_conserves_rewards(
+        (num_mint, duration, users, actions, initial_rules) in scenario_with(set_rule).prop_flat_map(|(num_mint, duration, users, actions)| {
+            (Just(num_mint), Just(duration), Just(users), Just(actions), prop::collection::vec(set_rule(users), 0..4))
+        }),
+    ) {
+        let mut model = Model::new(num_mint, duration, users);
+        for rule in &initial_rules {
+            model.apply(rule);
+        }
+        // base reward and set bonuses together stay within the funding, also when the rules change mid period
+        model.run(&actions);
+    }
+
+    #[test]
+    fn pauses_conserve_rewards(
+        (num_mint, duration, users, actions) in scenario_with(|_| prop_oneof![Just(Action::Pause), Just(Action::Resume)]),
+    ) {
+        let mut model = Model::new(num_mint, duration, users);
+        // pauses before, across and after the end of the periods
+        model.run(&actions);
+    }
+
+    #[test]
+    fn full_stake_funds_pay_out_the_funding(
+        num_mint in 1..=8u32,
+        duration in 1..=1_000_000u64,
+        steps in prop::collection::vec((0..=2_000_000u64, prop::option::of(1..=MAX_FUND), any::<bool>()), 1..8),
+    ) {
+        let mut model = Model::new(num_mint, duration, 1);
+        for _ in 0..num_mint {
+            model.apply(&Action::Stake(0));
+        }
+        // funds before and after the end of the running period, the stake time before them is unsettled unless claimed
+        for &(seconds, fund, claim) in &steps {
+            model.apply(&Action::Advance(seconds));
+            if let Some(amount) = fund {
+                model.apply(&Action::Fund(amount));
+            }
+            if claim {
+                model.apply(&Action::Claim(0));
+            }
+            model.check_accounts();
+        }
+        model.apply(&Action::Advance(model.pool.reward_duration_end.saturating_sub(model.now)));
+        model.apply(&Action::Claim(0));
+        model.check_accounts();
+
+        // every nft staked the whole time, the funding is paid out less the truncation of the rates and the updates
+        prop_assert_eq!(model.users[0].reward_owed, 0);
+        prop_assert!(model.funded - model.paid <= 2 * (steps.len() as u64 + 1));
+    }
+
+    #[test]
+    fn full_stake_dust_is_bounded(
+        num_mint in 1..=8u32,
+        duration in 1..=1_000_000u64,
+        amount in 1..=1_000_000_000_000u64,
+        steps in prop::collection::vec((0..=1_000_000u64, any::<bool>(), any::<bool>()), 0..8),
+    ) {
+        let mut model = Model::new(num_mint, duration, 1);
+        for _ in 0..num_mint {
+            model.apply(&Action::Stake(0));
+        }
+        model.apply(&Action::Fund(amount));
+        // claims before and after the end, the pool is paused or resumed in between
+        for (seconds, toggle, claim) in steps {
+            model.apply(&Action::Advance(seconds));
+            if toggle {
+                model.apply(if model.pool.paused { &Action::Resume } else { &Action::Pause });
+            }
+            if claim {
+                model.apply(&Action::Claim(0));
+            }
+        }
+        model.apply(&Action::Resume);
+        model.apply(&Action::Advance(model.pool.reward_duration_end.saturating_sub(model.now)));
+        model.apply(&Action::Claim(0));
+        model.check_accounts();
+
+        // every nft staked for the whole unpaused period, at most one token per nft second is left in the vault
+        prop_assert_eq!(model.users[0].reward_owed, 0);
+        prop_assert!(model.paid <= amount);
+        prop_assert!(amount - model.paid <= u64::from(num_mint) * duration);
+    }
+
+    #[test]
+    fn top_up_lasts_as_long_as_the_amount(
+        num_mint in 1..=8u32,
+        reward_rate_per_token in prop_oneof![Just(0), 1..=1_000 * PRECISION],
+        end in 0..=1_000_000u64,
+        now in 0..=1_000_000u64,
+        amount in 0..=1_000_000_000_000u64,
+    ) {
+        let pool = Pool {
+            reward_rate_per_token,
+            reward_duration_end: end,
+            ..Pool::default()
+        };
+        let per_second = reward_rate_per_token * num_mint as u128;
+        match topped_up_duration_end(&pool, num_mint, amount, now) {
+            Err(error) => {
+                prop_assert_eq!(error, ErrorCode::TopUpWithoutRate.into());
+                prop_assert!(reward_rate_per_token == 0 || now >= end);
+            }
+            Ok(new_end) => {
+                // the added seconds pay out at most the amount at the full precision rate, less than a second of emission is left
+                prop_assert!(now < end);
+                let added = (new_end - end) as u128;
+                let amount = amount as u128 * PRECISION;
+                prop_assert!(added * per_second <= amount);
+                prop_assert!(amount - added * per_second < per_second);
+            }
+        }
+    }
+
+    #[test]
+    fn loyalty_bonus_matches_the_weight_per_second(
+        step in 1..=50u64,
+        step_bps in 0..=2_000u32,
+        max_bps in 0..=10_000u32,
+        age in 0..=1_000u64,
+    ) {
+        let pool = Pool {
+            loyalty_step: step,
+            loyalty_step_bps: step_bps,
+            loyalty_max_bps: max_bps,
+            ..Pool::default()
+        };
+        // weight of every second of stake
+        let expected: u128 = (0..age)
+            .map(|t| std::cmp::min((t / step) as u128 * step_bps as u128, max_bps as u128))
+            .sum();
+        prop_assert_eq!(loyalty_bonus(&pool, age), expected);
+    }
+}
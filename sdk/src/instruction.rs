@@ -0,0 +1,592 @@
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::solana_program::{system_instruction, system_program, sysvar};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use nft_staking::{accounts, instruction, CountBonus, FundMode, Pool, User, ID};
+
+use crate::pda;
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn build_with_remaining(
+    accounts: impl ToAccountMetas,
+    remaining_accounts: Vec<AccountMeta>,
+    data: impl InstructionData,
+) -> Instruction {
+    let mut ix = build(accounts, data);
+    ix.accounts.extend(remaining_accounts);
+    ix
+}
+
+// create the config account, has to be in the same transaction as initialize_pool
+pub fn create_config_account(authority: &Pubkey, seed: &str, lamports: u64, num_mint: u32) -> Instruction {
+    create_config_account_with_mint_groups(authority, seed, lamports, num_mint, 0)
+}
+
+// config with `mint_group_space` bytes after the mint addresses for add_mint_group
+pub fn create_config_account_with_mint_groups(authority: &Pubkey, seed: &str, lamports: u64, num_mint: u32, mint_group_space: usize) -> Instruction {
+    let config = pda::config_address(authority, seed).unwrap();
+    system_instruction::create_account_with_seed(
+        authority,
+        &config,
+        authority,
+        seed,
+        lamports,
+        (crate::state::config_space(num_mint) + mint_group_space) as u64,
+        &ID,
+    )
+}
+
+pub fn initialize_pool(
+    authority: &Pubkey,
+    config: &Pubkey,
+    reward_mint: &Pubkey,
+    uuid: &str,
+    num_mint: u32,
+    reward_duration: u64,
+) -> Instruction {
+    let (pool_account, pool_bump) = pda::pool_address(authority, config);
+    let (reward_vault, reward_bump) = pda::reward_vault_address(&pool_account, authority, reward_mint);
+    build(
+        accounts::InitializePool {
+            authority: *authority,
+            pool_account,
+            config: *config,
+            reward_mint: *reward_mint,
+            reward_vault,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+            system_program: system_program::ID,
+        },
+        instruction::InitializePool {
+            _pool_bump: pool_bump,
+            uuid: uuid.to_string(),
+            num_mint,
+            _reward_bump: reward_bump,
+            reward_duration,
+        },
+    )
+}
+
+pub fn add_mint_addresses(authority: &Pubkey, pool_account: &Pubkey, config: &Pubkey, mint_addresses: Vec<Pubkey>, index: u32) -> Instruction {
+    build(
+        accounts::AddMintAddresses {
+            authority: *authority,
+            pool_account: *pool_account,
+            config: *config,
+        },
+        instruction::AddMintAddresses { mint_addresses, index },
+    )
+}
+
+// replaces the count bonus rules of the pool, at most MAX_COUNT_BONUS
+pub fn set_count_bonus(authority: &Pubkey, pool_account: &Pubkey, rules: Vec<CountBonus>) -> Instruction {
+    build(
+        accounts::Pause { authority: *authority, pool_account: *pool_account },
+        instruction::SetCountBonus { rules },
+    )
+}
+
+pub fn add_mint_group(authority: &Pubkey, pool_account: &Pubkey, config: &Pubkey, bonus_bps: u32, mints: Vec<Pubkey>) -> Instruction {
+    build(
+        accounts::AddMintAddresses {
+            authority: *authority,
+            pool_account: *pool_account,
+            config: *config,
+        },
+        instruction::AddMintGroup { bonus_bps, mints },
+    )
+}
+
+pub fn clear_mint_groups(authority: &Pubkey, pool_account: &Pubkey, config: &Pubkey) -> Instruction {
+    build(
+        accounts::AddMintAddresses {
+            authority: *authority,
+            pool_account: *pool_account,
+            config: *config,
+        },
+        instruction::ClearMintGroups {},
+    )
+}
+
+pub fn pause(authority: &Pubkey, pool_account: &Pubkey) -> Instruction {
+    build(
+        accounts::Pause { authority: *authority, pool_account: *pool_account },
+        instruction::Pause {},
+    )
+}
+
+pub fn resume(authority: &Pubkey, pool_account: &Pubkey) -> Instruction {
+    build(
+        accounts::Pause { authority: *authority, pool_account: *pool_account },
+        instruction::Resume {},
+    )
+}
+
+// `loyalty_step` 0 disables the loyalty bonus
+pub fn set_loyalty_ramp(authority: &Pubkey, pool_account: &Pubkey, loyalty_step: u64, loyalty_step_bps: u32, loyalty_max_bps: u32) -> Instruction {
+    build(
+        accounts::Pause { authority: *authority, pool_account: *pool_account },
+        instruction::SetLoyaltyRamp {
+            loyalty_step,
+            loyalty_step_bps,
+            loyalty_max_bps,
+        },
+    )
+}
+
+pub fn authorize_funder(authority: &Pubkey, pool_account: &Pubkey, funder_to_add: &Pubkey, cap: u64) -> Instruction {
+    let (funder_account, funder_bump) = pda::funder_address(pool_account, funder_to_add);
+    build(
+        accounts::AuthorizeFunder {
+            authority: *authority,
+            pool_account: *pool_account,
+            funder_account,
+            system_program: system_program::id(),
+        },
+        instruction::AuthorizeFunder { funder_bump, funder_to_add: *funder_to_add, cap },
+    )
+}
+
+pub fn set_funder_cap(authority: &Pubkey, pool_account: &Pubkey, funder: &Pubkey, cap: u64) -> Instruction {
+    build(
+        accounts::SetFunderCap {
+            authority: *authority,
+            pool_account: *pool_account,
+            funder_account: pda::funder_address(pool_account, funder).0,
+        },
+        instruction::SetFunderCap { cap },
+    )
+}
+
+pub fn deauthorize_funder(authority: &Pubkey, pool_account: &Pubkey, funder_to_remove: &Pubkey) -> Instruction {
+    build(
+        accounts::DeauthorizeFunder {
+            authority: *authority,
+            pool_account: *pool_account,
+            funder_account: pda::funder_address(pool_account, funder_to_remove).0,
+        },
+        instruction::DeauthorizeFunder { funder_to_remove: *funder_to_remove },
+    )
+}
+
+pub fn fund(funder: &Pubkey, pool_account: &Pubkey, pool: &Pool, funder_vault: &Pubkey, amount: u64, mode: FundMode) -> Instruction {
+    build(
+        accounts::Fund {
+            funder: *funder,
+            authority: pool.authority,
+            pool_account: *pool_account,
+            config: pool.config,
+            reward_vault: pool.reward_vault,
+            funder_vault: *funder_vault,
+            funder_account: pda::funder_address(pool_account, funder).0,
+            token_program: spl_token::id(),
+        },
+        instruction::Fund { amount, mode },
+    )
+}
+
+// reports the rate and end of a fund without transferring, simulate it and read the logs or the FundPreviewed event
+pub fn preview_fund(pool_account: &Pubkey, pool: &Pool, amount: u64, mode: FundMode) -> Instruction {
+    build(
+        accounts::PreviewFund { pool_account: *pool_account, config: pool.config },
+        instruction::PreviewFund { amount, mode },
+    )
+}
+
+// open to any signer, `donor_vault` is a reward token account of the donor
+pub fn donate(donor: &Pubkey, pool_account: &Pubkey, pool: &Pool, donor_vault: &Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::Donate {
+            donor: *donor,
+            pool_account: *pool_account,
+            config: pool.config,
+            reward_vault: pool.reward_vault,
+            donor_vault: *donor_vault,
+            token_program: spl_token::id(),
+        },
+        instruction::Donate { amount },
+    )
+}
+
+pub fn create_user(user: &Pubkey, pool_account: &Pubkey, uuid: &str) -> Instruction {
+    let (user_account, user_bump) = pda::user_address(pool_account, user);
+    let (mint_staked, mint_staked_bump) = pda::mint_staked_address(pool_account, &user_account, uuid);
+    build(
+        accounts::CreateUser {
+            user: *user,
+            pool_account: *pool_account,
+            user_account,
+            mint_staked,
+            rent: sysvar::rent::ID,
+            system_program: system_program::ID,
+        },
+        instruction::CreateUser {
+            _user_bump: user_bump,
+            _mint_staked_bump: mint_staked_bump,
+            uuid: uuid.to_string(),
+        },
+    )
+}
+
+// `uuid` seeds the new mint staked account, it has to differ from the uuid of the current one
+pub fn stake(staker: &Pubkey, pool_account: &Pubkey, pool: &Pool, user: &User, stake_from_account: &Pubkey, uuid: &str) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, staker);
+    let (mint_staked, mint_staked_bump) = pda::mint_staked_address(pool_account, &user_account, uuid);
+    build(
+        accounts::Stake {
+            staker: *staker,
+            pool_account: *pool_account,
+            config: pool.config,
+            authority: pool.authority,
+            user_account,
+            stake_from_account: *stake_from_account,
+            mint_staked,
+            current_mint_staked: user.mint_staked,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+            system_program: system_program::ID,
+        },
+        instruction::Stake { _mint_staked_bump: mint_staked_bump, uuid: uuid.to_string() },
+    )
+}
+
+// pass `reward_to_account` to claim the pending rewards in the same instruction
+pub fn unstake(
+    staker: &Pubkey,
+    pool_account: &Pubkey,
+    pool: &Pool,
+    user: &User,
+    unstake_from_account: &Pubkey,
+    uuid: &str,
+    reward_to_account: Option<&Pubkey>,
+) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, staker);
+    let (mint_staked, mint_staked_bump) = pda::mint_staked_address(pool_account, &user_account, uuid);
+    let remaining_accounts = match reward_to_account {
+        Some(reward_to_account) => vec![
+            AccountMeta::new(pool.reward_vault, false),
+            AccountMeta::new(*reward_to_account, false),
+        ],
+        None => vec![],
+    };
+    build_with_remaining(
+        accounts::Unstake {
+            staker: *staker,
+            pool_account: *pool_account,
+            config: pool.config,
+            authority: pool.authority,
+            user_account,
+            unstake_from_account: *unstake_from_account,
+            mint_staked,
+            current_mint_staked: user.mint_staked,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+            system_program: system_program::ID,
+        },
+        remaining_accounts,
+        instruction::Unstake { _mint_staked_bump: mint_staked_bump, uuid: uuid.to_string() },
+    )
+}
+
+pub fn stake_many(staker: &Pubkey, pool_account: &Pubkey, pool: &Pool, user: &User, stake_from_accounts: &[Pubkey], uuid: &str) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, staker);
+    let (mint_staked, mint_staked_bump) = pda::mint_staked_address(pool_account, &user_account, uuid);
+    build_with_remaining(
+        accounts::StakeMany {
+            staker: *staker,
+            pool_account: *pool_account,
+            config: pool.config,
+            authority: pool.authority,
+            user_account,
+            mint_staked,
+            current_mint_staked: user.mint_staked,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+            system_program: system_program::ID,
+        },
+        stake_from_accounts.iter().map(|x| AccountMeta::new(*x, false)).collect(),
+        instruction::StakeMany {
+            _mint_staked_bump: mint_staked_bump,
+            uuid: uuid.to_string(),
+            count: stake_from_accounts.len() as u32,
+        },
+    )
+}
+
+pub fn unstake_many(staker: &Pubkey, pool_account: &Pubkey, pool: &Pool, user: &User, unstake_from_accounts: &[Pubkey], uuid: &str) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, staker);
+    let (mint_staked, mint_staked_bump) = pda::mint_staked_address(pool_account, &user_account, uuid);
+    build_with_remaining(
+        accounts::UnstakeMany {
+            staker: *staker,
+            pool_account: *pool_account,
+            config: pool.config,
+            authority: pool.authority,
+            user_account,
+            mint_staked,
+            current_mint_staked: user.mint_staked,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+            system_program: system_program::ID,
+        },
+        unstake_from_accounts.iter().map(|x| AccountMeta::new(*x, false)).collect(),
+        instruction::UnstakeMany {
+            _mint_staked_bump: mint_staked_bump,
+            uuid: uuid.to_string(),
+            count: unstake_from_accounts.len() as u32,
+        },
+    )
+}
+
+pub fn emergency_unstake(staker: &Pubkey, pool_account: &Pubkey, pool: &Pool, user: &User, unstake_from_account: &Pubkey, uuid: &str) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, staker);
+    let (mint_staked, mint_staked_bump) = pda::mint_staked_address(pool_account, &user_account, uuid);
+    build(
+        accounts::Unstake {
+            staker: *staker,
+            pool_account: *pool_account,
+            config: pool.config,
+            authority: pool.authority,
+            user_account,
+            unstake_from_account: *unstake_from_account,
+            mint_staked,
+            current_mint_staked: user.mint_staked,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+            system_program: system_program::ID,
+        },
+        instruction::EmergencyUnstake { _mint_staked_bump: mint_staked_bump, uuid: uuid.to_string() },
+    )
+}
+
+pub fn claim(user: &Pubkey, pool_account: &Pubkey, pool: &Pool, user_state: &User, reward_to_account: &Pubkey) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, user);
+    build(
+        accounts::ClaimReward {
+            user: *user,
+            pool_account: *pool_account,
+            authority: pool.authority,
+            reward_vault: pool.reward_vault,
+            user_account,
+            mint_staked: user_state.mint_staked,
+            reward_to_account: *reward_to_account,
+            token_program: spl_token::id(),
+        },
+        instruction::Claim {},
+    )
+}
+
+// claim into the user associated token account, created by the program if needed
+pub fn claim_to_associated_token(user: &Pubkey, pool_account: &Pubkey, pool: &Pool, user_state: &User) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, user);
+    build(
+        accounts::ClaimRewardToAssociated {
+            user: *user,
+            pool_account: *pool_account,
+            authority: pool.authority,
+            reward_vault: pool.reward_vault,
+            user_account,
+            mint_staked: user_state.mint_staked,
+            reward_mint: pool.reward_mint,
+            reward_to_account: pda::associated_token_address(user, &pool.reward_mint),
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            system_program: system_program::ID,
+        },
+        instruction::ClaimToAssociatedToken {},
+    )
+}
+
+pub fn set_claim_delegate(user: &Pubkey, pool_account: &Pubkey, delegate: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, user);
+    build(
+        accounts::SetClaimDelegate {
+            user: *user,
+            pool_account: *pool_account,
+            user_account,
+        },
+        instruction::SetClaimDelegate { delegate: *delegate, recipient: *recipient },
+    )
+}
+
+pub fn claim_for(
+    claimer: &Pubkey,
+    user: &Pubkey,
+    pool_account: &Pubkey,
+    pool: &Pool,
+    user_state: &User,
+    reward_to_account: &Pubkey,
+) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, user);
+    build(
+        accounts::ClaimFor {
+            claimer: *claimer,
+            pool_account: *pool_account,
+            authority: pool.authority,
+            reward_vault: pool.reward_vault,
+            user_account,
+            mint_staked: user_state.mint_staked,
+            reward_to_account: *reward_to_account,
+            token_program: spl_token::id(),
+        },
+        instruction::ClaimFor {},
+    )
+}
+
+// simulate it and read the PendingRewardsView event from the logs
+pub fn view_pending_rewards(user: &Pubkey, pool_account: &Pubkey, user_state: &User) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, user);
+    build(
+        accounts::ViewPendingRewards {
+            pool_account: *pool_account,
+            user_account,
+            mint_staked: user_state.mint_staked,
+        },
+        instruction::ViewPendingRewards {},
+    )
+}
+
+// pass `reward_to_account` to claim the pending rewards in the same instruction
+pub fn close_user(user: &Pubkey, pool_account: &Pubkey, pool: &Pool, user_state: &User, reward_to_account: Option<&Pubkey>) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, user);
+    let remaining_accounts = match reward_to_account {
+        Some(reward_to_account) => vec![
+            AccountMeta::new(pool.reward_vault, false),
+            AccountMeta::new(*reward_to_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        None => vec![],
+    };
+    build_with_remaining(
+        accounts::CloseUser {
+            user: *user,
+            pool_account: *pool_account,
+            user_account,
+            mint_staked: user_state.mint_staked,
+        },
+        remaining_accounts,
+        instruction::CloseUser {},
+    )
+}
+
+pub fn close_mint_staked(user: &Pubkey, pool_account: &Pubkey, mint_staked: &Pubkey) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, user);
+    build(
+        accounts::CloseMintStaked {
+            user: *user,
+            pool_account: *pool_account,
+            user_account,
+            mint_staked: *mint_staked,
+        },
+        instruction::CloseMintStaked {},
+    )
+}
+
+// `funders` are pairs of funder and reward token account of the funder, refunded their share of the reward vault
+pub fn close_pool(
+    authority: &Pubkey,
+    pool_account: &Pubkey,
+    pool: &Pool,
+    refundee: &Pubkey,
+    reward_refundee: &Pubkey,
+    funders: &[(Pubkey, Pubkey)],
+) -> Instruction {
+    let remaining_accounts = funders
+        .iter()
+        .flat_map(|(funder, funder_vault)| {
+            vec![
+                AccountMeta::new(pda::funder_address(pool_account, funder).0, false),
+                AccountMeta::new(*funder_vault, false),
+            ]
+        })
+        .collect();
+    build_with_remaining(
+        accounts::ClosePool {
+            authority: *authority,
+            pool_account: *pool_account,
+            config: pool.config,
+            refundee: *refundee,
+            reward_refundee: *reward_refundee,
+            reward_vault: pool.reward_vault,
+            token_program: spl_token::id(),
+        },
+        remaining_accounts,
+        instruction::ClosePool {},
+    )
+}
+
+// first step of the migration of a pool on an older layout, migrate_pool has to follow in a later transaction
+pub fn begin_migrate_pool(authority: &Pubkey, pool_account: &Pubkey) -> Instruction {
+    let (migration, migration_bump) = pda::migration_address(pool_account);
+    build(
+        accounts::BeginMigratePool {
+            authority: *authority,
+            pool_account: *pool_account,
+            migration,
+            system_program: system_program::ID,
+        },
+        instruction::BeginMigratePool { _migration_bump: migration_bump },
+    )
+}
+
+pub fn migrate_pool(authority: &Pubkey, config: &Pubkey) -> Instruction {
+    let (pool_account, pool_bump) = pda::pool_address(authority, config);
+    let (migration, _) = pda::migration_address(&pool_account);
+    build(
+        accounts::MigratePool {
+            authority: *authority,
+            pool_account,
+            config: *config,
+            migration,
+            system_program: system_program::ID,
+        },
+        instruction::MigratePool { _pool_bump: pool_bump },
+    )
+}
+
+// first step of the migration of a user account on an older layout, the pool has to be migrated already
+pub fn begin_migrate_user(user: &Pubkey, pool_account: &Pubkey) -> Instruction {
+    let (user_account, _) = pda::user_address(pool_account, user);
+    let (migration, migration_bump) = pda::migration_address(&user_account);
+    build(
+        accounts::BeginMigrateUser {
+            user: *user,
+            pool_account: *pool_account,
+            user_account,
+            migration,
+            system_program: system_program::ID,
+        },
+        instruction::BeginMigrateUser { _migration_bump: migration_bump },
+    )
+}
+
+// `current_mint_staked` is the mint staked account of the old user account, `uuid` seeds its replacement
+pub fn migrate_user(user: &Pubkey, pool_account: &Pubkey, current_mint_staked: &Pubkey, uuid: &str) -> Instruction {
+    let (user_account, user_bump) = pda::user_address(pool_account, user);
+    let (migration, _) = pda::migration_address(&user_account);
+    let (mint_staked, mint_staked_bump) = pda::mint_staked_address(pool_account, &user_account, uuid);
+    build(
+        accounts::MigrateUser {
+            user: *user,
+            pool_account: *pool_account,
+            user_account,
+            migration,
+            current_mint_staked: *current_mint_staked,
+            mint_staked,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateUser {
+            _user_bump: user_bump,
+            _mint_staked_bump: mint_staked_bump,
+            uuid: uuid.to_string(),
+        },
+    )
+}
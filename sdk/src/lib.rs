@@ -0,0 +1,14 @@
+//! Rust client SDK for the nft_staking program
+//!
+//! - `pda`: program derived addresses of the pool, reward vault, user, mint staked and migration accounts
+//! - `instruction`: typed instruction builders
+//! - `state`: account deserializers, config mint groups and the stake history of a user
+//! - `rewards`: pending reward computation, shared with the program
+
+pub mod instruction;
+pub mod pda;
+pub mod rewards;
+pub mod state;
+
+pub use nft_staking::{Config, CountBonus, FundMode, Funder, Migration, MintGroup, MintStaked, Pool, StakeHistory, User, ID};
+pub use nft_staking::{PREFIX, PREFIX_FUNDER, PREFIX_MIGRATION, PREFIX_MINT, PREFIX_USER};
@@ -0,0 +1,48 @@
+use anchor_lang::solana_program::pubkey::{Pubkey, PubkeyError};
+use nft_staking::{ID, PREFIX, PREFIX_FUNDER, PREFIX_MIGRATION, PREFIX_MINT, PREFIX_USER};
+
+// pool account, seeds = [PREFIX, authority, config]
+pub fn pool_address(authority: &Pubkey, config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PREFIX.as_bytes(), authority.as_ref(), config.as_ref()], &ID)
+}
+
+// reward vault, seeds = [PREFIX, pool, authority, reward_mint]
+pub fn reward_vault_address(pool: &Pubkey, authority: &Pubkey, reward_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PREFIX.as_bytes(), pool.as_ref(), authority.as_ref(), reward_mint.as_ref()],
+        &ID,
+    )
+}
+
+// user account, seeds = [PREFIX_USER, pool, user]
+pub fn user_address(pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PREFIX_USER.as_bytes(), pool.as_ref(), user.as_ref()], &ID)
+}
+
+// mint staked account, seeds = [PREFIX_MINT, pool, user_account, uuid]
+pub fn mint_staked_address(pool: &Pubkey, user_account: &Pubkey, uuid: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PREFIX_MINT.as_bytes(), pool.as_ref(), user_account.as_ref(), uuid.as_bytes()],
+        &ID,
+    )
+}
+
+// migration account holding the old data of a pool or user account, seeds = [PREFIX_MIGRATION, account]
+pub fn migration_address(account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PREFIX_MIGRATION.as_bytes(), account.as_ref()], &ID)
+}
+
+// funder account of an authorized funder, seeds = [PREFIX_FUNDER, pool, funder]
+pub fn funder_address(pool: &Pubkey, funder: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PREFIX_FUNDER.as_bytes(), pool.as_ref(), funder.as_ref()], &ID)
+}
+
+// config account, created with seed by the authority and owned by the program
+pub fn config_address(authority: &Pubkey, seed: &str) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_with_seed(authority, seed, &ID)
+}
+
+// associated token account of the owner for the mint
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(owner, mint)
+}
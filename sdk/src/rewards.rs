@@ -0,0 +1,15 @@
+use nft_staking::{MintStaked, Pool, User};
+
+pub use nft_staking::utils::{fund_schedule, per_token_earned};
+
+// pending reward of the user at `now`, same computation as update_rewards in the program
+// `mint_staked` is the current mint staked account of the user, its stake history gives the loyalty bonus
+pub fn pending_rewards(pool: &Pool, user: &User, mint_staked: &MintStaked, now: u64) -> u64 {
+    let (reward_earned_pending, _paused_duration) = nft_staking::utils::pending_rewards(pool, user, &mint_staked.stake_history, now);
+    reward_earned_pending
+}
+
+// pending reward plus reward owed from previous partial payouts, what the next claim pays out if the vault allows
+pub fn claimable_rewards(pool: &Pool, user: &User, mint_staked: &MintStaked, now: u64) -> u64 {
+    pending_rewards(pool, user, mint_staked, now).saturating_add(user.reward_owed)
+}
@@ -0,0 +1,71 @@
+use anchor_lang::prelude::*;
+use nft_staking::constants::PUBKEY_SIZE;
+use nft_staking::utils::read_older_layout;
+use nft_staking::{get_mint_groups, Config, Funder, MintGroup, MintStaked, Pool, User, CONFIG_SIZE_START, POOL_SIZE, USER_SIZE};
+use std::convert::TryInto;
+
+pub fn pool(data: &[u8]) -> std::result::Result<Pool, ProgramError> {
+    Pool::try_deserialize(&mut &data[..])
+}
+
+pub fn config(data: &[u8]) -> std::result::Result<Config, ProgramError> {
+    Config::try_deserialize(&mut &data[..])
+}
+
+pub fn user(data: &[u8]) -> std::result::Result<User, ProgramError> {
+    User::try_deserialize(&mut &data[..])
+}
+
+pub fn mint_staked(data: &[u8]) -> std::result::Result<MintStaked, ProgramError> {
+    MintStaked::try_deserialize(&mut &data[..])
+}
+
+pub fn funder(data: &[u8]) -> std::result::Result<Funder, ProgramError> {
+    Funder::try_deserialize(&mut &data[..])
+}
+
+// pool account written with an older layout, waiting for migrate_pool
+pub fn older_pool(data: &[u8]) -> std::result::Result<Pool, ProgramError> {
+    read_older_layout(data, POOL_SIZE)
+}
+
+// user account written with an older layout, waiting for migrate_user
+pub fn older_user(data: &[u8]) -> std::result::Result<User, ProgramError> {
+    read_older_layout(data, USER_SIZE)
+}
+
+// total seconds `mint` was staked by the user up to `now`, across unstakes, 0 if the user never staked it
+pub fn staked_seconds(mint_staked: &MintStaked, mint: &Pubkey, now: u64) -> u64 {
+    mint_staked
+        .stake_history
+        .iter()
+        .find(|x| x.mint == *mint)
+        .map_or(0, |x| x.staked_seconds_at(now))
+}
+
+// space of a config account holding `num_mint` mint addresses
+pub fn config_space(num_mint: u32) -> usize {
+    CONFIG_SIZE_START + 4 + PUBKEY_SIZE * num_mint as usize
+}
+
+// space after the mint addresses for mint groups of `group_sizes` mints
+pub fn mint_group_space(group_sizes: &[usize]) -> usize {
+    4 + group_sizes.iter().map(|x| 4 + 4 + PUBKEY_SIZE * x).sum::<usize>()
+}
+
+// mint groups stored after the mint addresses, empty if the config has no room for them
+pub fn mint_groups(data: &[u8]) -> std::result::Result<Vec<MintGroup>, ProgramError> {
+    get_mint_groups(data, config(data)?.num_mint)
+}
+
+// mint addresses stored after the Config struct, the count is the u32 at CONFIG_SIZE_START
+pub fn config_mint_addresses(data: &[u8]) -> std::result::Result<Vec<Pubkey>, ProgramError> {
+    let count_bytes = data
+        .get(CONFIG_SIZE_START..CONFIG_SIZE_START + 4)
+        .ok_or(ProgramError::AccountDataTooSmall)?;
+    let count = u32::from_le_bytes(count_bytes.try_into().unwrap()) as usize;
+    let start = CONFIG_SIZE_START + 4;
+    let end = start + count * PUBKEY_SIZE;
+    let mints = data.get(start..end).ok_or(ProgramError::AccountDataTooSmall)?;
+    Ok(mints.chunks(PUBKEY_SIZE).map(Pubkey::new).collect())
+}